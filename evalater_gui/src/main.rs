@@ -3,6 +3,7 @@
 use eframe::{egui::*, NativeOptions};
 use clipboard::{ClipboardContext, ClipboardProvider};
 use mcpp_core::compiler;
+use mcpp_core::compiler::diagnostics::Diagnostic;
 
 #[derive(Default, Clone)]
 pub struct MyApp {
@@ -12,7 +13,7 @@ pub struct MyApp {
 
 impl eframe::App for MyApp {
     fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
-        egui::CentralPanel::default().show(&ctx, |ui| {
+        egui::CentralPanel::default().show(ctx, |ui| {
             ui.vertical_centered(|ui| {
                 ui.label("2025/04/13 by Kinokov Shotaskovich");
                 ui.label("Evaluater");
@@ -23,11 +24,18 @@ impl eframe::App for MyApp {
                             let mut ctx: ClipboardContext = ClipboardProvider::new().unwrap();
                             ctx.set_contents(o).unwrap();
                         },
-                        Err(e) => {self.error = format!("{}", e);}
+                        Err(e) => {
+                            self.error = Diagnostic::all(&e)
+                                .iter()
+                                .map(|d| d.render(&self.text, "input"))
+                                .collect::<Vec<String>>()
+                                .join("\n\n");
+                        }
                     }
                 }
                 ui.label(
                     RichText::from(self.error.clone())
+                        .monospace()
                         .color(Color32::LIGHT_RED)
                 );
             });
@@ -1,28 +1,41 @@
 use ast::serialiser::MCFunction;
 use ast::serialiser::MCFunctionizable;
+use ast::serialiser::macros::{self, Macro};
 use ast::syntax_analyser;
 use ast::SyntaxError;
 use evaluater::scoreboard::command_ast::CommandAST;
 use evaluater::Oper;
+use evaluater::scoreboard::magnification;
+
+use std::collections::{BTreeSet, HashMap};
+use std::rc::Rc;
 
 pub mod save;
 pub mod evaluater;
 pub mod tokeniser;
 pub mod ast;
+pub mod diagnostics;
+pub mod target;
 
 use crate::compiler::ast::serialiser::IToken;
+use crate::compiler::tokeniser::LexError;
 use crate::evaluater::Type;
 use crate::evaluater::Scoreboard;
+use target::{Feature, TargetVersion};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
     // Identifier and literal
     Ident(String), // variable / function name
     Int(i32),      // num+
-    Flt(f32),      // num*.num+
+    Flt(f64),      // num*.num+
     Bln(bool),     // true / false
     Str(String),   // "..."
     MCId(String),  // $...:... or $(minecraft:)...
+    Label(String), // 'name
+
+    // Comment
+    DocComment(String), // /// ...
 
     // Operator
     Asn, // =
@@ -49,6 +62,10 @@ pub enum Token {
     Arr, // ->
     FArr,// =>
 
+    // Ranges
+    DotDot,   // ..
+    DotDotEq, // ..=
+
     // Delimiters
     Comma,     // ,
     Dot,       // .
@@ -63,54 +80,196 @@ pub enum Token {
 
     // Keywords
     Let, // Values binding
-    Fn, If, Else, While, For, // Sentense specifiers
-    IntType, FltType, BlnType, NoneType, // Types. Float containt how many decimal places does it ensures.
+    As, // Casting a value
+    Fn, If, Else, While, For, In, Step, // Sentense specifiers
+    IntType, FltType, BlnType, StrType, NoneType, // Types. A float can specify its decimal places as `float<N>`.
     Return, // Returning a value
+    Break, Continue, // Leaving a loop
 }
 impl Token {
     fn to_type(&self) -> Option<Type> {
         match self {
             Token::IntType => Some(Type::Int),
-            Token::FltType => Some(Type::Float),
+            Token::FltType => Some(Type::FLOAT),
             Token::BlnType => Some(Type::Bool),
+            Token::StrType => Some(Type::Str),
+            Token::NoneType => Some(Type::None),
             _ => None
         }
     }
 }
+/// A location in the source. Lines and columns count from 1.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Span {
+    pub start: usize, // byte offset
+    pub end: usize,   // byte offset (exclusive)
+    pub line: usize,
+    pub column: usize
+}
+impl Span {
+    /// The default span, which means the location isn't known yet
+    pub fn is_unknown(&self) -> bool {
+        self == &Span::default()
+    }
+    /// A span from the beginning of self to the end of other
+    pub fn to(&self, other:&Span) -> Span {
+        if self.is_unknown() {
+            return *other;
+        }
+        Span { end: other.end.max(self.end), ..*self }
+    }
+}
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+#[derive(Debug, PartialEq, Clone)]
+pub struct Spanned<T> {
+    pub inner: T,
+    pub span: Span
+}
+impl<T> Spanned<T> {
+    pub fn new(inner:T, span:Span) -> Self {
+        Spanned { inner, span }
+    }
+}
+impl<T> std::ops::Deref for Spanned<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+pub type SpannedToken = Spanned<Token>;
+
 #[derive(Debug)]
-pub enum CompileError {
+pub enum CompileErrorKind {
+    LexicalErrorsOccured(Vec<LexError>),
     ASyntaxErrorOccured(SyntaxError),
     InvalidTokenInAFormula(Token),
     EmptyFormulaGiven,
     UndefinedIdentifierReferenced(String),
     UnknownTypeSpecialised(Token),
     LHSDoesntSatisfyValidFormat,
-    InvalidRHS(IToken),
-    TheTokenIsntValue(IToken),
+    InvalidRHS(Box<IToken>),
+    TheTokenIsntValue(Box<IToken>),
     InvalidFormulaStructure(String),
-    UnsupportedLiteralType(IToken),
+    UnsupportedLiteralType(Box<IToken>),
     UndefinedOperation(Type, Oper, Type),
+    UndefinedUnaryOperation(Oper, Type),
     UnbalancedParentheses,
-    TheTypeOfAIndentifierWontBeConfirmed(String)
+    TheTypeOfAIndentifierWontBeConfirmed(String),
+    /// The function, the numbers of its parameters and the arguments given, and where it's defined
+    ArgumentCountMismatch(String, usize, usize, Span),
+    ReturnOutsideOfFunction,
+    /// The variable, its type, the type given, and where it's declared
+    MismatchedAssignment(String, Type, Type, Span),
+    FloatOutOfRange(String, Type),
+    ImplicitNarrowing(Type, Type),
+    JumpOutsideOfLoop(String),
+    UndefinedLabel(String),
+    UnsupportedByTarget(String, Feature, TargetVersion),
+    /// The function, and where it's defined first
+    FunctionRedefined(String, Span),
+    ReservedFunctionName(String),
+    UnjoinableString,
+    NonTailRecursion(String),
+    MissingReturnValue(Type),
+    MissingReturn(String, Type)
+}
+impl CompileErrorKind {
+    /// The error raised at the span, which is unknown if it's `Span::default()`
+    pub fn at(self, span:Span) -> CompileError {
+        Spanned::new(self, span)
+    }
+}
+/// An error with where it's raised, which is unknown until `at` gives it
+pub type CompileError = Spanned<CompileErrorKind>;
+impl CompileError {
+    /// Gives the span to an error raised without knowing where it is
+    pub fn at(mut self, span:Span) -> Self {
+        if self.span.is_unknown() {
+            self.span = span;
+        }
+        self
+    }
+}
+impl From<Vec<LexError>> for CompileError {
+    fn from(value: Vec<LexError>) -> Self {
+        let span = value.first().map(|e| e.span()).unwrap_or_default();
+        CompileErrorKind::LexicalErrorsOccured(value).at(span)
+    }
+}
+impl From<SyntaxError> for CompileError {
+    fn from(value: SyntaxError) -> Self {
+        let span = value.span();
+        CompileErrorKind::ASyntaxErrorOccured(value).at(span)
+    }
 }
 impl std::fmt::Display for CompileError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let result = match self {
-            CompileError::ASyntaxErrorOccured(a) => a.to_string(),
-            CompileError::InvalidTokenInAFormula(t) => format!("An invalid token, {:?} exists in the formula.", t),
-            CompileError::EmptyFormulaGiven => String::from("An empty formula was given."),
-            CompileError::UndefinedIdentifierReferenced(id) => format!("A identifer, {} was referenced but undefined.", id),
-            CompileError::UnknownTypeSpecialised(t) => format!("A token, {:?} isn't valid as type specifier.", t),
-            CompileError::LHSDoesntSatisfyValidFormat => String::from("The left hand side doesn't satisfy the valid format."),
-            CompileError::InvalidRHS(t) => format!("The rhs, {} can't be assined onto the lhs.", t),
-            CompileError::TheTokenIsntValue(t) => format!("The token, {:?} isn't value.", t),
-            CompileError::InvalidFormulaStructure(s) => s.clone(),
-            CompileError::UnsupportedLiteralType(t) => format!("The token, {} isn't supported as a literal type.", t),
-            CompileError::UndefinedOperation(l, o, h) => format!("An unsupported calcation occured, {} {} {}", l, o, h),
-            CompileError::UnbalancedParentheses => String::from("The number of opening and closing parentheses does not match."),
-            CompileError::TheTypeOfAIndentifierWontBeConfirmed(t) => format!("The type of an identifer, {} won't be confirmed at the time of compiling.", t)
-        };
-        write!(f, "{}", result)
+        match &self.inner {
+            CompileErrorKind::LexicalErrorsOccured(e) => write!(
+                f, "{}", e.iter().map(|e| e.to_string()).collect::<Vec<String>>().join("\n")
+            ),
+            CompileErrorKind::ASyntaxErrorOccured(a) => write!(f, "{}", a),
+            _ => write!(f, "{}: {}", self.span, self.message())
+        }
+    }
+}
+impl CompileErrorKind {
+    /// The description of the error without its location
+    pub fn message(&self) -> String {
+        match self {
+            CompileErrorKind::LexicalErrorsOccured(e) => e.iter().map(|e| e.message()).collect::<Vec<String>>().join("\n"),
+            CompileErrorKind::ASyntaxErrorOccured(a) => a.message(),
+            CompileErrorKind::InvalidTokenInAFormula(t) => format!("the token `{:?}` can't be used in a formula", t),
+            CompileErrorKind::EmptyFormulaGiven => String::from("expected an expression, found nothing"),
+            CompileErrorKind::UndefinedIdentifierReferenced(id) => format!("cannot find `{}` in this scope", id),
+            CompileErrorKind::UnknownTypeSpecialised(t) => format!("`{:?}` is not a type", t),
+            CompileErrorKind::LHSDoesntSatisfyValidFormat => String::from("the left hand side of the assignment is invalid"),
+            CompileErrorKind::InvalidRHS(t) => format!("the value `{}` can't be assigned here", t),
+            CompileErrorKind::TheTokenIsntValue(t) => format!("expected a value, found `{}`", t),
+            CompileErrorKind::InvalidFormulaStructure(s) => s.clone(),
+            CompileErrorKind::UnsupportedLiteralType(t) => format!("`{}` is not supported as a literal", t),
+            CompileErrorKind::UndefinedOperation(l, o, h) => format!("the operation `{} {} {}` is not defined", l, o, h),
+            CompileErrorKind::UndefinedUnaryOperation(o, t) if o.is_postfix() => format!("the operation `{} {}` is not defined", t, o),
+            CompileErrorKind::UndefinedUnaryOperation(o, t) => format!("the operation `{}{}` is not defined", o, t),
+            CompileErrorKind::UnbalancedParentheses => String::from("the parentheses are unbalanced"),
+            CompileErrorKind::TheTypeOfAIndentifierWontBeConfirmed(t) => format!("the type of `{}` can't be inferred", t),
+            CompileErrorKind::ArgumentCountMismatch(n, e, g, _) => format!(
+                "the function `{}` takes {} argument{} but {} {} given",
+                n, e, if *e == 1 {""} else {"s"}, g, if *g == 1 {"was"} else {"were"}
+            ),
+            CompileErrorKind::ReturnOutsideOfFunction => String::from("`return` is used outside of a function"),
+            CompileErrorKind::MismatchedAssignment(n, e, g, _) => format!("mismatched types: `{}` is `{}` but `{}` was given", n, e, g),
+            CompileErrorKind::FloatOutOfRange(v, t) => {
+                let places = t.places();
+                let max = i32::MAX as f64 / magnification(places) as f64;
+                format!(
+                    "`{}` overflows a score as `{}`, which ranges within ±{:.*}; try fewer decimal places",
+                    v, t, places as usize, max
+                )
+            },
+            CompileErrorKind::ImplicitNarrowing(g, e) => format!(
+                "`{}` is narrowed into `{}` implicitly, which isn't allowed in the strict mode; write `as` to convert it", g, e
+            ),
+            CompileErrorKind::JumpOutsideOfLoop(k) => format!("`{}` is used outside of a loop", k),
+            CompileErrorKind::UndefinedLabel(l) => format!("use of undeclared label `'{}`", l),
+            CompileErrorKind::UnsupportedByTarget(c, f, t) => format!(
+                "{} needs {} of Minecraft {} or later, but the target is {}", c, f, f.since(), t
+            ),
+            CompileErrorKind::FunctionRedefined(n, _) => format!("the function `{}` is defined multiple times", n),
+            CompileErrorKind::ReservedFunctionName(n) => format!("the name `{}` is reserved for the functions the compiler generates", n),
+            CompileErrorKind::UnjoinableString => String::from(
+                "a string with `\"` or `\\` can't be used in a program joining strings, which puts them into quotes as they are"
+            ),
+            CompileErrorKind::NonTailRecursion(n) => format!(
+                "`{}` is called recursively outside of `return`, which would overwrite the scores of the call not finished yet", n
+            ),
+            CompileErrorKind::MissingReturnValue(t) => format!("`return` has no value, but the function returns `{}`", t),
+            CompileErrorKind::MissingReturn(n, t) => format!("the function `{}` may reach its end without returning `{}`", n, t)
+        }
     }
 }
 pub struct Compiler {
@@ -118,7 +277,34 @@ pub struct Compiler {
     pub compiled: Vec<MCFunction>,
     pub variables: Vec<Scoreboard>,
     pub functions: Vec<MCFunction>,
-    pub scope: Vec<String>
+    pub scope: Vec<String>,
+    pub returning: Option<Scoreboard>,
+    /// Where the variables are declared, keyed by the name of their scoreboards
+    pub declarations: HashMap<String, Span>,
+    /// The constants referred by the compiled commands, which are set on load
+    pub constants: BTreeSet<i32>,
+    /// Whether dropping digits without `as` is an error, e.g. a float assigned to an int
+    pub strict: bool,
+    /// The version of Minecraft, which decides the commands the compiled ones can use
+    pub target: TargetVersion,
+    /// The loops being compiled from the outermost, with their labels and control flags
+    loops: Vec<(Option<String>, Scoreboard)>,
+    /// The control flags set by `break` or `continue`, which the following statements have to check
+    jumps: Vec<Scoreboard>,
+    /// The number of `return` compiled in the current function, which tells if a block may return
+    return_count: usize,
+    /// The calls in the functions: the caller, the callee, whether it's the whole value of `return`, and where it is
+    calls: Vec<(String, String, bool, Span)>,
+    /// Whether the next call compiled is the whole value of `return`
+    tail_call: bool,
+    /// The temporaries of the blocks around in the current function, freed after the blocks end,
+    /// which `return 1` frees instead since nothing after it runs
+    held_temps: Vec<Scoreboard>,
+    temp_count: usize,
+    block_counts: HashMap<Vec<String>, usize>,
+    /// The first string literal with `"` or `\`, which can't be joined by a function macro
+    quoted_literal: Option<Span>,
+    macros: HashMap<String, Rc<dyn Macro>>
 }
 impl From<&str> for Compiler {
     fn from(value: &str) -> Self {
@@ -127,15 +313,38 @@ impl From<&str> for Compiler {
             compiled: Vec::new(),
             variables: Vec::new(),
             functions: Vec::new(),
-            scope: Vec::new()
+            scope: Vec::new(),
+            returning: None,
+            declarations: HashMap::new(),
+            constants: BTreeSet::new(),
+            strict: false,
+            target: TargetVersion::default(),
+            loops: Vec::new(),
+            jumps: Vec::new(),
+            return_count: 0,
+            calls: Vec::new(),
+            tail_call: false,
+            held_temps: Vec::new(),
+            temp_count: 0,
+            block_counts: HashMap::new(),
+            quoted_literal: None,
+            macros: HashMap::from([("native".to_string(), Rc::new(macros::Native) as Rc<dyn Macro>)])
         }
     }
 }
 impl Compiler {
+    /// Makes the macro callable as `name!(...)`, replacing the former one of the same name
+    pub fn register_macro(&mut self, name:&str, definition:impl Macro + 'static) {
+        self.macros.insert(name.to_string(), Rc::new(definition));
+    }
+    fn get_macro(&self, name:&str) -> Option<Rc<dyn Macro>> {
+        self.macros.get(name).cloned()
+    }
     fn get_score(&self, name:&String) -> Option<&Scoreboard> {
         self
             .variables
             .iter()
+            .rev()
             .find(|score| &score.name == name)
     }
     fn get_func(&self, name:&String) -> Option<&MCFunction> {
@@ -144,30 +353,46 @@ impl Compiler {
             .iter()
             .find(|func| &func.name == name)
     }
+    /// The number of the next temporary scoreboard, which is unique in the compilation
+    fn next_temp_id(&mut self) -> usize {
+        self.temp_count += 1;
+        self.temp_count - 1
+    }
+    /// The number of the next block in the current scope, such as 1 of `main/while_1`
+    fn next_block_id(&mut self) -> usize {
+        let count = self.block_counts.entry(self.scope.clone()).or_insert(0);
+        *count += 1;
+        *count - 1
+    }
+    /// Forgets the variables defined in the current scope and returns the commands freeing them
     fn leave_current_scope(&mut self) -> Vec<CommandAST> {
-        let mut res:Vec<CommandAST> = Vec::new();
-        for v in 0..=self.variables.len() {
-            if self.variables.get(v).unwrap().scope.len() >= self.scope.len() {
-                res.extend(self.variables.remove(v).free());
-            }
-        }
-        res
+        let (leaving, staying):(Vec<Scoreboard>, Vec<Scoreboard>) = std::mem::take(&mut self.variables)
+            .into_iter()
+            .partition(|v| v.scope.starts_with(&self.scope));
+        self.variables = staying;
+        leaving.iter().flat_map(|v| v.free()).collect()
     }
     fn serialise_mcfunction<T:MCFunctionizable>(&mut self, mcfunctionizable:&T) -> Result<(), CompileError> {
         let compiled = mcfunctionizable.mcfunctionate(self)?;
         self.compiled.push(compiled.clone());
-        self.functions.push(compiled);
+        // The signature declared before the body is replaced
+        match self.functions.iter_mut().find(|f| f.name == compiled.name) {
+            Some(declared) => *declared = compiled,
+            None => self.functions.push(compiled)
+        }
         Ok(())
     }
     pub fn evaluate(mut self, target:String) -> Result<String, CompileError> {
-        let mut s_analyser = syntax_analyser
-            ::SyntaxAnalyser
-            ::from(tokeniser::tokenize(target));
-        let codeblock = match s_analyser.get_block() {
-            Ok(o) => o,
-            Err(e) => Err(CompileError::ASyntaxErrorOccured(e))?
-        };
-        let mcfunc = codeblock.mcfunctionate(&mut self)?;
-        Ok(mcfunc.inside.clone())
+        let mut s_analyser = syntax_analyser::SyntaxAnalyser::from(tokeniser::tokenize(target)?);
+        let codeblock = s_analyser.get_block()?;
+        let mcfunc = codeblock.compile_named("main".to_string(), &mut self)?;
+        self.check_recursion()?;
+        // The objectives and the constants have to be ready before the evaluation
+        let mut evaluated = format!("{}\n{}", self.load_function().inside, mcfunc.inside);
+        // The functions it calls follow it, each of which has to be saved at the path in its header
+        for mcfunction in &self.compiled {
+            evaluated.push_str(&format!("\n\n# {}:{}\n{}", self.namespace, mcfunction.get_full_path(), mcfunction.inside));
+        }
+        Ok(evaluated)
     }
 }
\ No newline at end of file
@@ -1,19 +1,23 @@
 pub mod serialiser;
 pub mod syntax_analyser;
+#[cfg(test)]
+mod reference;
 
 use super::Type;
 use crate::compiler::Oper;
 use crate::compiler::Token;
+use crate::compiler::{Span, Spanned};
 
 #[derive(Debug, Clone)]
 pub enum FToken {
     Int(i32),
-    Flt(f32),
+    Flt(f64),
     Bln(bool),
     Scr(String),
     Fnc(String, Tuple),
     Mcr(String, Tuple),
     Str(String),
+    Type(Type),
     Oper(Oper),
     LParen,
     RParen
@@ -24,12 +28,10 @@ impl FToken {
             self,
             FToken::Int(_) | FToken::Flt(_) |
             FToken::Bln(_) | FToken::Scr(_) |
-            FToken::Fnc(_, _) | FToken::Mcr(_, _)
+            FToken::Str(_) | FToken::Fnc(_, _) |
+            FToken::Mcr(_, _)
         )
     }
-    fn is_operator(&self) -> bool {
-        matches!(self, FToken::Oper(_))
-    }
 }
 impl std::fmt::Display for FToken {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -39,6 +41,7 @@ impl std::fmt::Display for FToken {
             FToken::Bln(b) => b.to_string(),
             FToken::Scr(s) => s.clone(),
             FToken::Str(s) => s.clone(),
+            FToken::Type(t) => t.to_string(),
             FToken::Oper(o) => o.to_str().to_string(),
             FToken::LParen => "(".to_string(),
             FToken::RParen => ")".to_string(),
@@ -51,74 +54,174 @@ impl std::fmt::Display for FToken {
 
 #[derive(Debug, Clone)]
 pub enum SyntaxError {
-    ExpectedAToken(String),
-    EmptyFormula,
-    InvalidFormAs(String),
-    UndefinedOperationFound(Type, Oper, Type),
-    NotEnoughOperand,
-    OperatorAtInvalidPosition(Oper),
-    UnbalancedBraces,
-    UnbalancedParentheses,
-    TokenEndsUnexpectedly,
-    ALineMustntStartWith(Token),
-    InvalidTokenInAFormula(Token),
-    ArgumentCountMismatch,
+    ExpectedAToken(String, Span),
+    EmptyFormula(Span),
+    InvalidFormAs(String, Span),
+    UndefinedOperationFound(Type, Oper, Type, Span),
+    NotEnoughOperand(Span),
+    OperatorAtInvalidPosition(Oper, Span),
+    UnbalancedBraces(Span),
+    UnbalancedParentheses(Span),
+    TokenEndsUnexpectedly(Span),
+    ALineMustntStartWith(Token, Span),
+    InvalidTokenInAFormula(Token, Span),
+    ArgumentCountMismatch(Span),
+}
+impl SyntaxError {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::ExpectedAToken(_, s) |
+            Self::EmptyFormula(s) |
+            Self::InvalidFormAs(_, s) |
+            Self::UndefinedOperationFound(_, _, _, s) |
+            Self::NotEnoughOperand(s) |
+            Self::OperatorAtInvalidPosition(_, s) |
+            Self::UnbalancedBraces(s) |
+            Self::UnbalancedParentheses(s) |
+            Self::TokenEndsUnexpectedly(s) |
+            Self::ALineMustntStartWith(_, s) |
+            Self::InvalidTokenInAFormula(_, s) |
+            Self::ArgumentCountMismatch(s) => *s
+        }
+    }
 }
 impl std::fmt::Display for SyntaxError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", match self {
-            Self::ExpectedAToken(t) => format!("A token, {} is expected.", t),
-            Self::EmptyFormula => format!("Empty formula was given."),
-            Self::InvalidFormAs(t) => format!("The given tokens have an invalid form as {}", t),
-            Self::UndefinedOperationFound(l, o, r) => format!("An undefined operation, {} {} {} occured.", l, o, r),
-            Self::NotEnoughOperand => format!("Not enough operand. Add a operand or remove the last operator."),
-            Self::OperatorAtInvalidPosition(o) => format!("An operator, {} is in invalid position.", o),
-            Self::UnbalancedBraces => format!("Unbalanced braces found. Please make sure that it's closed."),
-            Self::UnbalancedParentheses => format!("Unbalanced parentheses found. Please make sure that it's closed."),
-            Self::TokenEndsUnexpectedly => format!("Token was ended unexpectedly. This syntax perhaps expects more tokens."),
-            Self::ALineMustntStartWith(t) => format!("A line mustn't starts with a token, {:?}.", t),
-            Self::InvalidTokenInAFormula(t) => format!("The token, {:?} doesn't constract formulas. It mustn't be in a formula.", t),
-            Self::ArgumentCountMismatch => format!("The function was given fewer or too many arguments.")
-        })
+        write!(f, "{}: {}", self.span(), self.message())
+    }
+}
+impl SyntaxError {
+    /// The description of the error without its location
+    pub fn message(&self) -> String {
+        match self {
+            Self::ExpectedAToken(t, _) => format!("expected {}", t),
+            Self::EmptyFormula(_) => String::from("expected an expression, found nothing"),
+            Self::InvalidFormAs(t, _) => format!("this is not a valid {}", t),
+            Self::UndefinedOperationFound(l, o, r, _) => format!("the operation `{} {} {}` is not defined", l, o, r),
+            Self::NotEnoughOperand(_) => String::from("an operator is missing its operand"),
+            Self::OperatorAtInvalidPosition(o, _) => format!("the operator `{}` is in an invalid position", o),
+            Self::UnbalancedBraces(_) => String::from("this brace is never closed"),
+            Self::UnbalancedParentheses(_) => String::from("the parentheses are unbalanced"),
+            Self::TokenEndsUnexpectedly(_) => String::from("the source ended unexpectedly"),
+            Self::ALineMustntStartWith(t, _) => format!("a statement can't start with `{:?}`", t),
+            Self::InvalidTokenInAFormula(t, _) => format!("the token `{:?}` can't be used in a formula", t),
+            Self::ArgumentCountMismatch(_) => String::from("the number of arguments is wrong")
+        }
     }
 }
 #[derive(Debug, Clone)]
-enum AST {
+enum Ast {
     Formula(Formula),
     CodeBlock(CodeBlock),
     IfSyntax(IfSyntax),
     WhileSyntax(WhileSyntax),
+    ForSyntax(ForSyntax),
     LetStatement(VariableDefinement),
-    Assignment(Assignment)
+    Assignment(Assignment),
+    FunctionDefinement(FunctionDefinement),
+    Return(ReturnStatement),
+    Jump(JumpStatement)
+}
+impl Ast {
+    fn span(&self) -> Span {
+        match self {
+            Ast::Formula(f) => f.span,
+            Ast::CodeBlock(c) => c.span,
+            Ast::IfSyntax(i) => i.span,
+            Ast::WhileSyntax(w) => w.span,
+            Ast::ForSyntax(f) => f.span,
+            Ast::LetStatement(l) => l.span,
+            Ast::Assignment(a) => a.span,
+            Ast::FunctionDefinement(f) => f.span,
+            Ast::Return(r) => r.span,
+            Ast::Jump(j) => j.span
+        }
+    }
+    /// Whether every way through the statement ends with `return`
+    fn always_returns(&self) -> bool {
+        match self {
+            Ast::Return(_) => true,
+            Ast::CodeBlock(c) => c.always_returns(),
+            Ast::IfSyntax(i) => i.block.always_returns() && i.else_block.as_ref().is_some_and(|e| e.always_returns()),
+            // `while true` is left only by `break` or `return`
+            Ast::WhileSyntax(w) => {
+                matches!(w.condition.formula_tokens[..], [Spanned { inner: FToken::Bln(true), .. }])
+                    && !w.block.breaks(&w.label, true)
+            },
+            _ => false
+        }
+    }
+    /// Whether a `break` in the statement leaves the loop of the label,
+    /// which is the innermost one around the statement unless the `break` is inside another loop
+    fn breaks(&self, label:&Option<String>, innermost:bool) -> bool {
+        match self {
+            Ast::Jump(j) if !j.is_continue => match &j.label {
+                Some(l) => label.as_ref() == Some(l),
+                None => innermost
+            },
+            Ast::CodeBlock(c) => c.breaks(label, innermost),
+            Ast::IfSyntax(i) => i.block.breaks(label, innermost) || i.else_block.as_ref().is_some_and(|e| e.breaks(label, innermost)),
+            Ast::WhileSyntax(w) => w.block.breaks(label, false),
+            Ast::ForSyntax(f) => f.block.breaks(label, false),
+            _ => false
+        }
+    }
 }
 #[derive(Debug, Clone)]
 struct Formula {
-    formula_tokens: Vec<FToken>
-}
-impl From<Vec<FToken>> for Formula {
-    fn from(value: Vec<FToken>) -> Self {
-        Formula { formula_tokens: value }
-    }
+    formula_tokens: Vec<Spanned<FToken>>,
+    pub span: Span
 }
 impl Formula {
-    fn to_rpn(self) -> Result<RPNFormula, SyntaxError> {
-        let mut queue:Vec<&FToken> = Vec::new();
-        let mut stack:Vec<&FToken> = Vec::new();
+    fn new(formula_tokens:Vec<Spanned<FToken>>, span:Span) -> Self {
+        Formula { formula_tokens, span }
+    }
+    fn into_rpn(self) -> Result<RPNFormula, SyntaxError> {
+        let mut queue:Vec<&Spanned<FToken>> = Vec::new();
+        let mut stack:Vec<&Spanned<FToken>> = Vec::new();
 
         if self.formula_tokens.is_empty() {
-            return Err(SyntaxError::EmptyFormula);
+            return Err(SyntaxError::EmptyFormula(self.span));
         }
+        // Whether the next token has to be an operand, or a unary operator before it
+        let mut expects_operand = true;
         for current in &self.formula_tokens {
             if current.is_value() {
+                expects_operand = false;
                 queue.push(current);
                 continue;
-            } else if current.is_operator() {
+            } else if let FToken::Oper(o) = &current.inner {
+                // A postfix operator is calculated at once after the prefix ones of its operand
+                if o.is_postfix() {
+                    if expects_operand {
+                        return Err(SyntaxError::OperatorAtInvalidPosition(o.clone(), current.span));
+                    }
+                    while let Some(Spanned { inner: FToken::Oper(top), .. }) = stack.last() {
+                        if top.get_priority() < o.get_priority() {
+                            break;
+                        }
+                        queue.push(stack.pop().unwrap());
+                    }
+                    queue.push(current);
+                    continue;
+                }
+                if o.is_unary() != expects_operand {
+                    return Err(SyntaxError::OperatorAtInvalidPosition(o.clone(), current.span));
+                }
+                expects_operand = true;
+                // A unary operator has no left operand to wait for
+                if o.is_unary() {
+                    stack.push(current);
+                    continue;
+                }
                 while let Some(top) = stack.last() {
-                    if !matches!(top, FToken::Oper(_)) {
+                    if !matches!(top.inner, FToken::Oper(_)) {
                         break;
                     }
-                    if let (FToken::Oper(l), FToken::Oper(r)) = (current, top) {
-                        if l.get_priority() < r.get_priority() {
+                    if let (FToken::Oper(l), FToken::Oper(r)) = (&current.inner, &top.inner) {
+                        // The former one of the same priority is calculated first if it's left associative
+                        if l.get_priority() < r.get_priority()
+                            || (l.get_priority() == r.get_priority() && l.is_left_associative()) {
                             queue.push(stack.pop().unwrap());
                         } else {
                             break;
@@ -130,76 +233,116 @@ impl Formula {
                 stack.push(current);
                 continue;
             } else {
-                match current {
+                match current.inner {
                     FToken::LParen => {
                         stack.push(current);
                     },
                     FToken::RParen => {
+                        expects_operand = false;
                         loop {
-                            let top = stack.pop().unwrap();
-                            match top {
+                            let top = stack
+                                .pop()
+                                .ok_or(SyntaxError::UnbalancedParentheses(current.span))?;
+                            match top.inner {
                                 FToken::LParen => break,
                                 _ => queue.push(top),
                             }
                         }
                     },
+                    // A type is only an argument of a macro
+                    FToken::Type(_) => {
+                        return Err(SyntaxError::InvalidFormAs("value".to_string(), current.span));
+                    },
                     _ => {
                         unreachable!()
                     }
                 }
             }
         }
-        while !stack.is_empty() {
-            queue.push(stack.pop().unwrap());
+        while let Some(top) = stack.pop() {
+            queue.push(top);
         }
         Ok(RPNFormula {
             formula_tokens: queue
-                .iter()
-                .map(|t| t.clone().clone())
-                .collect::<Vec<FToken>>()
+                .into_iter()
+                .cloned()
+                .collect::<Vec<Spanned<FToken>>>(),
+            span: self.span
         })
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct RPNFormula {
-    formula_tokens: Vec<FToken>
+    formula_tokens: Vec<Spanned<FToken>>,
+    pub span: Span
 }
 
 #[derive(Debug, Clone)]
 pub struct CodeBlock {
-    inside: Vec<AST>
+    inside: Vec<Ast>,
+    pub span: Span
+}
+
+impl CodeBlock {
+    /// Whether the block can't reach its end, which a function returning a value mustn't
+    fn always_returns(&self) -> bool {
+        self.inside.iter().any(|s| s.always_returns())
+    }
+    fn breaks(&self, label:&Option<String>, innermost:bool) -> bool {
+        self.inside.iter().any(|s| s.breaks(label, innermost))
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct IfSyntax {
     condition: RPNFormula,
-    block: CodeBlock
+    block: CodeBlock,
+    else_block: Option<CodeBlock>,
+    pub span: Span
 }
 
 #[derive(Debug, Clone)]
 pub struct WhileSyntax {
+    label: Option<String>,
     condition: RPNFormula,
-    block: CodeBlock
+    block: CodeBlock,
+    pub span: Span
+}
+
+/// `for variable in start..end step n`, where `..=` includes the end
+#[derive(Debug, Clone)]
+pub struct ForSyntax {
+    label: Option<String>,
+    variable: String,
+    start: RPNFormula,
+    end: RPNFormula,
+    inclusive: bool,
+    step: i32,
+    block: CodeBlock,
+    pub span: Span
 }
 
 #[derive(Debug, Clone)]
 pub struct VariableDefinement {
     identifier: String,
     datatype: Option<Type>,
-    initialise: Option<Assignment>
+    initialise: Option<Assignment>,
+    pub span: Span
 }
 
 #[derive(Debug, Clone)]
 pub struct Assignment {
     lhs: String,
-    rhs: RPNFormula
+    rhs: RPNFormula,
+    pub span: Span
 }
 
 #[derive(Debug, Clone)]
 pub struct Arguments {
     name: String,
-    datatype: Type
+    datatype: Type,
+    pub span: Span
 }
 
 #[derive(Debug, Clone)]
@@ -207,10 +350,59 @@ pub struct FunctionDefinement {
     func_name: String,
     datatype: Type,
     arguments: Vec<Arguments>,
-    block: CodeBlock
+    block: CodeBlock,
+    pub span: Span
+}
+
+#[derive(Debug, Clone)]
+pub struct ReturnStatement {
+    value: Option<RPNFormula>,
+    pub span: Span
+}
+
+/// `break` or `continue`, which refers the innermost loop unless it's labelled, e.g. `break 'outer;`
+#[derive(Debug, Clone)]
+pub struct JumpStatement {
+    is_continue: bool,
+    label: Option<String>,
+    pub span: Span
 }
 
 #[derive(Debug, Clone)]
 pub struct Tuple {
-    inside : Vec<Formula>
+    inside : Vec<Formula>,
+    pub span : Span
+}
+impl Tuple {
+    pub fn len(&self) -> usize {
+        self.inside.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.inside.is_empty()
+    }
+    /// The span of the argument, or of the whole tuple if it doesn't exist
+    pub fn span_at(&self, index:usize) -> Span {
+        self.inside.get(index).map(|f| f.span).unwrap_or(self.span)
+    }
+    /// The argument if it's just a token
+    fn single(&self, index:usize) -> Option<&FToken> {
+        match self.inside.get(index)?.formula_tokens.as_slice() {
+            [token] => Some(&token.inner),
+            _ => None
+        }
+    }
+    /// The argument if it's a string literal
+    pub fn string_at(&self, index:usize) -> Option<&str> {
+        match self.single(index)? {
+            FToken::Str(s) => Some(s),
+            _ => None
+        }
+    }
+    /// The argument if it's a type name, such as `int`
+    pub fn type_at(&self, index:usize) -> Option<Type> {
+        match self.single(index)? {
+            FToken::Type(t) => Some(*t),
+            _ => None
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,276 @@
+use rand::rngs::StdRng;
+use rand::seq::IndexedRandom;
+use rand::{Rng, SeedableRng};
+
+use super::syntax_analyser::SyntaxAnalyser;
+use super::{FToken, RPNFormula, Ast};
+use crate::compiler::{tokeniser, Oper};
+use crate::evaluater::scoreboard::arithmetic_operation::Arithmetic;
+use crate::evaluater::scoreboard::comparison_operation::Comparison;
+use crate::evaluater::scoreboard::logical_operation::Logical;
+use crate::evaluater::scoreboard::unary_operation::Unary;
+use crate::evaluater::scoreboard::{FLOAT_MAGNIFICATION, FLOAT_PLACES};
+use crate::evaluater::Type;
+use crate::vm::VirtualMachine;
+
+const MAG:i64 = FLOAT_MAGNIFICATION as i64;
+
+/// A value evaluated in Rust, whose float is a fixed-point number as a score is
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Value { Int(i32), Float(i32), Bool(bool) }
+impl Value {
+    fn score(self) -> i32 {
+        match self {
+            Value::Int(i) | Value::Float(i) => i,
+            Value::Bool(b) => b as i32
+        }
+    }
+}
+
+/// Every step is calculated in a score, so the result is undefined if any of them overflows
+fn fit(value:i64) -> Option<i32> {
+    i32::try_from(value).ok()
+}
+
+/// The division of a score, which is undefined if it's divided by zero
+fn floor_div(left:i64, right:i64) -> Option<i64> {
+    if right == 0 {
+        return None;
+    }
+    let quotient = left / right;
+    Some(if left % right != 0 && (left < 0) != (right < 0) {quotient - 1} else {quotient})
+}
+
+fn arithmetic(operator:&Arithmetic, left:Value, right:Value) -> Option<Value> {
+    match (left, right) {
+        (Value::Int(l), Value::Int(r)) => {
+            let (l, r) = (l as i64, r as i64);
+            Some(Value::Int(fit(match operator {
+                Arithmetic::Add => l + r,
+                Arithmetic::Rem => l - r,
+                Arithmetic::Mul => l * r,
+                Arithmetic::Div => floor_div(l, r)?,
+                Arithmetic::Sur => l - floor_div(l, r)? * r
+            })?))
+        },
+        // The int is calculated as a float, then the result is floored
+        (Value::Int(l), Value::Float(_)) => match arithmetic(operator, Value::Float(fit(l as i64 * MAG)?), right)? {
+            Value::Float(f) => Some(Value::Int(fit(floor_div(f as i64, MAG)?)?)),
+            _ => None
+        },
+        (Value::Float(l), Value::Int(r)) => match operator {
+            Arithmetic::Add | Arithmetic::Rem => arithmetic(operator, left, Value::Float(fit(r as i64 * MAG)?)),
+            Arithmetic::Mul => Some(Value::Float(fit(l as i64 * r as i64)?)),
+            Arithmetic::Div => Some(Value::Float(fit(floor_div(l as i64, r as i64)?)?)),
+            Arithmetic::Sur => None
+        },
+        (Value::Float(l), Value::Float(r)) => {
+            let (l, r) = (l as i64, r as i64);
+            Some(Value::Float(fit(match operator {
+                Arithmetic::Add => l + r,
+                Arithmetic::Rem => l - r,
+                Arithmetic::Mul => floor_div(fit(l * r)? as i64, MAG)?,
+                Arithmetic::Div => floor_div(fit(l * MAG)? as i64, r)?,
+                Arithmetic::Sur => None?
+            })?))
+        },
+        _ => None
+    }
+}
+
+fn comparison(operator:&Comparison, left:Value, right:Value) -> Option<Value> {
+    let (l, r) = match (left, right) {
+        (Value::Int(l), Value::Float(r)) => (fit(l as i64 * MAG)?, r),
+        (Value::Float(l), Value::Int(r)) => (l, fit(r as i64 * MAG)?),
+        (l, r) => (l.score(), r.score())
+    };
+    Some(Value::Bool(match operator {
+        Comparison::Gt => l > r,
+        Comparison::Ge => l >= r,
+        Comparison::Lt => l < r,
+        Comparison::Le => l <= r,
+        Comparison::Eq => l == r,
+        Comparison::Neq => l != r
+    }))
+}
+
+fn logical(operator:&Logical, left:Value, right:Value) -> Option<Value> {
+    match (operator, left, right) {
+        (Logical::And, Value::Bool(l), Value::Bool(r)) => Some(Value::Bool(l && r)),
+        (Logical::Or, Value::Bool(l), Value::Bool(r)) => Some(Value::Bool(l || r)),
+        _ => None
+    }
+}
+
+fn unary(operator:&Unary, operand:Value) -> Option<Value> {
+    match (operator, operand) {
+        (Unary::Neg, Value::Int(i)) => Some(Value::Int(fit(-(i as i64))?)),
+        (Unary::Neg, Value::Float(f)) => Some(Value::Float(fit(-(f as i64))?)),
+        (Unary::Pos, Value::Int(_) | Value::Float(_)) => Some(operand),
+        (Unary::Not, Value::Bool(b)) => Some(Value::Bool(!b)),
+        (Unary::Cast(Type::Int), Value::Float(f)) => Some(Value::Int(fit(floor_div(f as i64, MAG)?)?)),
+        (Unary::Cast(Type::Int), _) => Some(Value::Int(operand.score())),
+        (Unary::Cast(Type::Float(FLOAT_PLACES)), Value::Float(_)) => Some(operand),
+        (Unary::Cast(Type::Float(FLOAT_PLACES)), _) => Some(Value::Float(fit(operand.score() as i64 * MAG)?)),
+        (Unary::Cast(Type::Bool), _) => Some(Value::Bool(operand.score() != 0)),
+        _ => None
+    }
+}
+
+/// Evaluates a formula in Rust, which is None if its result is undefined
+fn evaluate(rpn:&RPNFormula) -> Option<Value> {
+    let mut stack = Vec::new();
+    for token in &rpn.formula_tokens {
+        let value = match &token.inner {
+            FToken::Int(i) => Value::Int(*i),
+            FToken::Flt(f) => Value::Float(fit((*f * MAG as f64).round() as i64)?),
+            FToken::Bln(b) => Value::Bool(*b),
+            FToken::Oper(Oper::Unary(u)) => unary(u, stack.pop()?)?,
+            FToken::Oper(operator) => {
+                let right = stack.pop()?;
+                let left = stack.pop()?;
+                match operator {
+                    Oper::Arithmetic(a) => arithmetic(a, left, right)?,
+                    Oper::Comparison(c) => comparison(c, left, right)?,
+                    Oper::Logical(l) => logical(l, left, right)?,
+                    Oper::Unary(_) => unreachable!()
+                }
+            },
+            _ => return None
+        };
+        stack.push(value);
+    }
+    stack.pop()
+}
+
+fn program(formula:&str) -> String {
+    format!("{{ let result = {}; }}", formula)
+}
+
+fn parse(formula:&str) -> RPNFormula {
+    let tokens = tokeniser::tokenize(program(formula)).unwrap();
+    let block = SyntaxAnalyser::from(tokens).get_block().unwrap();
+    match &block.inside[0] {
+        Ast::LetStatement(l) => l.initialise.clone().unwrap().rhs,
+        _ => unreachable!()
+    }
+}
+
+/// Compiles the formula and runs it on the virtual machine after the load function
+fn run_compiled(formula:&str) -> Option<i32> {
+    let compiled = crate::compile(&format!("let result = {};", formula))
+        .unwrap_or_else(|e| panic!("`{}` wasn't compiled: {}", formula, e));
+    let mut vm = VirtualMachine::new().functions("MCPP", &compiled);
+    for function in ["MCPP:__load", "MCPP:main"] {
+        vm.run(function).unwrap_or_else(|e| panic!("`{}` failed: {}", formula, e));
+    }
+    vm.last_score("#main.result", "MCPP.var")
+}
+
+/// Asserts the compiled formula results the same as the reference, and returns if it's defined
+fn assert_same(formula:&str) -> bool {
+    let Some(expected) = evaluate(&parse(formula)) else {
+        return false;
+    };
+    assert_eq!(run_compiled(formula), Some(expected.score()), "`{}` should be {:?}", formula, expected);
+    true
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Kind { Int, Float, Bool }
+
+fn generate(rng:&mut StdRng, kind:Kind, depth:u32) -> String {
+    let number = |rng:&mut StdRng| *[Kind::Int, Kind::Float].choose(rng).unwrap();
+    if depth == 0 || rng.random_bool(0.25) {
+        return match kind {
+            Kind::Int => rng.random_range(0..=20).to_string(),
+            Kind::Float => format!("{}.{}", rng.random_range(0..=9), rng.random_range(0..1000)),
+            Kind::Bool => rng.random_bool(0.5).to_string()
+        };
+    }
+    if rng.random_bool(0.1) {
+        let from = *[Kind::Int, Kind::Float, Kind::Bool].choose(rng).unwrap();
+        let to = match kind {
+            Kind::Int => "int",
+            Kind::Float => "float",
+            Kind::Bool => "bool"
+        };
+        return format!("({} as {})", generate(rng, from, depth - 1), to);
+    }
+    if rng.random_bool(0.15) {
+        let operator = match kind {
+            Kind::Bool => "!",
+            _ => *["-", "+"].choose(rng).unwrap()
+        };
+        return format!("{}{}", operator, generate(rng, kind, depth - 1));
+    }
+    let (left, operator, right) = match kind {
+        Kind::Int => {
+            let operator = *["+", "-", "*", "/", "%"].choose(rng).unwrap();
+            (Kind::Int, operator, if operator == "%" {Kind::Int} else {number(rng)})
+        },
+        Kind::Float => (Kind::Float, *["+", "-", "*", "/"].choose(rng).unwrap(), number(rng)),
+        Kind::Bool => match rng.random_range(0..3) {
+            0 => (number(rng), *["<", "<=", ">", ">=", "==", "!="].choose(rng).unwrap(), number(rng)),
+            1 => (Kind::Bool, *["==", "!="].choose(rng).unwrap(), Kind::Bool),
+            _ => (Kind::Bool, *["&", "|"].choose(rng).unwrap(), Kind::Bool)
+        }
+    };
+    format!("({} {} {})", generate(rng, left, depth - 1), operator, generate(rng, right, depth - 1))
+}
+
+#[test]
+fn scaling_test() {
+    for formula in [
+        "2.5 * 2", "7.5 / 2", "2.5 + 2", "2.5 - 2",
+        "3 * 1.5", "3 / 1.5", "3 + 1.5", "3 - 1.5",
+        "2.5 * 1.5", "7.5 / 2.5", "0.29 + 0.0",
+        "(1 + 2) * (0.5 + 0.25)", "(2.5 * 1.0) * (1 + 1)", "(3 + 0) * (1.5 + 0.0)",
+        "(7 - 0) / (2 + 0)", "(0 - 7) % 3", "1 < 1.5", "(1 + 0) < (0.5 + 0.5)", "2 == 2.0",
+        "-3 % 2", "1.5 + -2.25", "3 - -1", "-(1 + 2) * -2.5", "!(1 < 2) | !false", "+-+2",
+        "-2.5 as int", "(0 - 2.5) as int", "(1 + 1) as float / 4", "(0.5 + 0) as bool", "(true & true) as int + 1"
+    ] {
+        assert!(assert_same(formula), "`{}` should be defined", formula);
+    }
+}
+
+#[test]
+fn random_test() {
+    let mut rng = StdRng::seed_from_u64(0x4d43_5050);
+    let mut defined = 0;
+    for _ in 0..500 {
+        let kind = *[Kind::Int, Kind::Float, Kind::Bool].choose(&mut rng).unwrap();
+        if assert_same(&generate(&mut rng, kind, 3)) {
+            defined += 1;
+        }
+    }
+    // Most of the formulas have to be tested, not skipped as undefined
+    assert!(defined > 400, "only {} formulas were defined", defined);
+}
+
+/// The fixed-point score of a literal, which is made from its digits to be exact
+fn scale_digits(literal:&str, places:u32) -> i64 {
+    let (whole, fraction) = literal.split_once('.').unwrap_or((literal, ""));
+    let fraction = format!("{:0<width$}", fraction, width = places as usize + 1);
+    let scaled = format!("{}{}", whole, &fraction[..places as usize]).parse::<i64>().unwrap();
+    if fraction.as_bytes()[places as usize] >= b'5' {scaled + 1} else {scaled}
+}
+
+#[test]
+fn places_test() {
+    for (literal, places) in [
+        ("12345.6789", 4), ("1234.567891", 6), ("98765.432", 4), ("0.29", 4), ("2.675", 2), ("1.005", 2),
+        ("21474.83647", 5), ("0.123456789", 9), ("7.5", 1), ("99999999.99", 1), ("3.14159", 3)
+    ] {
+        let compiled = crate::compile(&format!("let result:float<{}> = {};", places, literal)).unwrap();
+        let mut vm = VirtualMachine::new().functions("MCPP", &compiled);
+        for function in ["MCPP:__load", "MCPP:main"] {
+            vm.run(function).unwrap();
+        }
+        assert_eq!(
+            vm.last_score("#main.result", "MCPP.var").map(i64::from),
+            Some(scale_digits(literal, places)),
+            "`{}` as float<{}>", literal, places
+        );
+    }
+}
@@ -1,43 +1,49 @@
-mod macros;
+pub mod macros;
 
 use core::fmt;
 use std::vec;
 
 use crate::compiler::Compiler;
-use crate::evaluater::scoreboard::command_ast::{ExecuteConstructer, FormulaConstructer, Serialise};
+use crate::compiler::target::Feature;
+use crate::evaluater::scoreboard::command_ast::{ExecuteConstructer, FormulaConstructer};
+use crate::evaluater::scoreboard::arithmetic_operation::{self, CONCAT_FUNCTION};
 use crate::evaluater::scoreboard::comparison_operation::Comparison;
-use crate::evaluater::scoreboard::generate_random_id;
-use crate::{compiler::CompileError, evaluater::Scoreboard};
+use crate::evaluater::scoreboard::unary_operation::Unary;
+use crate::{compiler::{CompileError, CompileErrorKind}, evaluater::Scoreboard};
 
 use super::*;
 use crate::evaluater::scoreboard::{self, command_ast::CommandAST};
 use crate::compiler::evaluater::Operator;
 
+/// The function run on load, prefixed not to collide with the functions in the source
+pub const LOAD_FUNCTION_NAME:&str = "__load";
+
 /// Interpreted Token
 #[derive(Debug, Clone)]
 pub enum IToken {
     Int(i32),
-    Flt(f32),
+    Flt(f64),
     Bln(bool),
     Scr(Scoreboard),
     Str(String),
     Fnc(MCFunction, Tuple),
-    Mcr(String, Tuple),
+    Mcr(String, Tuple, Type),
     Oper(Oper),
     LParen,
     RParen
 }
 impl IToken {
     fn is_value(&self) -> bool {
-        matches!(self, IToken::Scr(_) | IToken::Fnc(_, _) | IToken::Mcr(_, _) | IToken::Int(_) | IToken::Flt(_) | IToken::Bln(_))
+        matches!(self, IToken::Scr(_) | IToken::Fnc(_, _) | IToken::Mcr(_, _, _) | IToken::Int(_) | IToken::Flt(_) | IToken::Bln(_) | IToken::Str(_))
     }
     fn get_datatype(&self) -> Option<Type> {
         match self {
             Self::Bln(_) => Some(Type::Bool),
-            Self::Flt(_) => Some(Type::Float),
-            Self::Fnc(f, _) => Some(f.returning_type.clone()),
+            Self::Flt(_) => Some(Type::FLOAT),
+            Self::Fnc(f, _) => Some(f.returning_type),
             Self::Int(_) => Some(Type::Int),
-            Self::Scr(s) => Some(s.datatype.clone()),
+            Self::Mcr(_, _, t) => Some(*t),
+            Self::Scr(s) => Some(s.datatype),
             Self::Str(_) => Some(Type::Str),
             _ => None
         }
@@ -51,7 +57,7 @@ impl fmt::Display for IToken {
             IToken::Fnc(s, _) => format!("{}(...)", s.name),
             IToken::Int(i) => i.to_string(),
             IToken::LParen => "(".to_string(),
-            IToken::Mcr(s, _) => format!("{}!(...)", s),
+            IToken::Mcr(s, _, _) => format!("{}!(...)", s),
             IToken::Oper(o) => o.to_str().to_string(),
             IToken::RParen => ")".to_string(),
             IToken::Scr(s) => s.get_mcname(),
@@ -69,17 +75,24 @@ impl FToken {
             Self::Scr(s) => Ok(IToken::Scr(
                 compiler
                     .get_score(&s)
-                    .ok_or(CompileError::UndefinedIdentifierReferenced(s))?
+                    .ok_or(CompileErrorKind::UndefinedIdentifierReferenced(s).at(Span::default()))?
                     .clone()
             )),
             Self::Fnc(f, a) => Ok(IToken::Fnc(
                 compiler
                     .get_func(&f)
-                    .ok_or(CompileError::UndefinedIdentifierReferenced(f))?
+                    .ok_or(CompileErrorKind::UndefinedIdentifierReferenced(f).at(Span::default()))?
                     .clone(),
                 a.clone()
             )),
-            Self::Mcr(m, a) => Ok(IToken::Mcr(m, a)),
+            Self::Mcr(m, a) => {
+                let datatype = compiler
+                    .get_macro(&m)
+                    .ok_or(CompileErrorKind::UndefinedIdentifierReferenced(format!("{}!", m)).at(Span::default()))?
+                    .returning_type(&a, compiler)?;
+                Ok(IToken::Mcr(m, a, datatype))
+            },
+            Self::Type(t) => Err(CompileErrorKind::InvalidFormulaStructure(format!("the type `{}` isn't a value", t)).at(Span::default())),
             Self::Oper(o) => Ok(IToken::Oper(o)),
             Self::LParen => Ok(IToken::LParen),
             Self::RParen => Ok(IToken::RParen)
@@ -95,153 +108,1035 @@ pub struct MCFunction {
     callment_prefix: String,
     preprocess: String,
     postprocess: String,
-    returning_type: Type
+    returning_type: Type,
+    arguments: Vec<Scoreboard>,
+    defined_at: Span,
+    /// Whether it may `return` from the function it's in, which its caller has to follow
+    returns: bool
 }
 impl MCFunction {
+    pub fn get_full_path(&self) -> String {
+        let mut full_path = self.path.clone();
+        full_path.push(self.name.clone());
+        full_path.join("/")
+    }
     fn call(&self, compiler:&Compiler) -> String {
-        format!(
-            "{}\n{} function {}/{}",
-            self.postprocess,
-            self.callment_prefix,
-            compiler.namespace,
-            self.path.join("/")
-        )
+        join_lines(vec![self.preprocess.clone(), self.callment(compiler), self.postprocess.clone()])
+    }
+    /// The command calling it, which returns as well if it has returned
+    fn callment(&self, compiler:&Compiler) -> String {
+        let function = format!("{}:{}", compiler.namespace, self.get_full_path());
+        if !self.returns {
+            return format!("{}function {}", self.callment_prefix, function);
+        }
+        let conditions = self.callment_prefix.strip_suffix("run ").unwrap_or("execute ");
+        format!("{}if function {} run return 1", conditions, function)
+    }
+    /// A function in the current scope which just runs the commands
+    fn of_commands(name:String, commands:&[CommandAST], compiler:&mut Compiler, defined_at:Span) -> MCFunction {
+        MCFunction {
+            name,
+            inside: compiler.c_ast_to_string(commands),
+            path: compiler.scope.clone(),
+            callment_prefix: String::new(),
+            preprocess: String::new(),
+            postprocess: String::new(),
+            returning_type: Type::None,
+            arguments: Vec::new(),
+            defined_at,
+            returns: false
+        }
+    }
+    fn return_slot(&self) -> Scoreboard {
+        let mut scope = self.path.clone();
+        scope.push(self.name.clone());
+        scoreboard::get_return_slot(scope, self.returning_type)
+    }
+    /// Evaluates the arguments into the callee's scoreboards then calls it
+    fn call_with(&self, compiler:&mut Compiler, arguments:&Tuple) -> Result<Vec<CommandAST>, CompileError> {
+        if arguments.inside.len() != self.arguments.len() {
+            return Err(CompileErrorKind::ArgumentCountMismatch(
+                self.name.clone(), self.arguments.len(), arguments.inside.len(), self.defined_at
+            ).at(arguments.span));
+        }
+        // Every argument is evaluated before any of them is stored,
+        // so a nested call to the same function can't overwrite the former ones.
+        let mut commands = Vec::new();
+        let mut evaluated = Vec::new();
+        for (param, formula) in self.arguments.iter().zip(arguments.inside.iter()) {
+            let temp = scoreboard::get_calc_temp(param.datatype, compiler.next_temp_id());
+            let rpn = match formula.clone().into_rpn() {
+                Ok(o) => o,
+                Err(e) => Err(CompileError::from(e))?
+            };
+            commands.extend(rpn.to_calc_reserv(compiler)?.serialise(compiler, &temp)?);
+            evaluated.push(temp);
+        }
+        for (param, temp) in self.arguments.iter().zip(evaluated.iter()) {
+            commands.extend(param.assign(&IToken::Scr(temp.clone()))?);
+            commands.extend(temp.free());
+        }
+        commands.push(CommandAST::Native(self.call(compiler)));
+        Ok(commands)
     }
 }
 pub trait MCFunctionizable {
     fn mcfunctionate(&self, compiler:&mut Compiler) -> Result<MCFunction, CompileError>;
 }
-impl Serialisable for AST {
+impl Serialisable for Ast {
+    /// An error without its location is pointed at the statement it occurred in
     fn serialise(&self, compiler:&mut Compiler) -> Result<Vec<CommandAST>, CompileError> {
-        match self {
-            AST::CodeBlock(_) | AST::IfSyntax(_) | AST::WhileSyntax(_) => {
-                let mcfunctionated = self.mcfunctionate(compiler)?;
-                let callment = mcfunctionated.call(compiler);
-                compiler.compiled.push(mcfunctionated);
-                Ok(vec![CommandAST::Native(callment)])
-            },
-            AST::Assignment(a) => a.serialise(compiler),
-            AST::Formula(f) => f.serialise(compiler),
-            AST::LetStatement(l) => l.serialise(compiler)
-        }
+        let serialised = match self {
+            Ast::CodeBlock(_) | Ast::IfSyntax(_) | Ast::WhileSyntax(_) | Ast::ForSyntax(_) => self
+                .mcfunctionate(compiler)
+                .map(|mcfunctionated| {
+                    let callment = mcfunctionated.call(compiler);
+                    compiler.compiled.push(mcfunctionated);
+                    vec![CommandAST::Native(callment)]
+                }),
+            Ast::FunctionDefinement(f) => compiler
+                .serialise_mcfunction(f)
+                .map(|_| Vec::new()),
+            Ast::Assignment(a) => a.serialise(compiler),
+            Ast::Formula(f) => f.serialise(compiler),
+            Ast::LetStatement(l) => l.serialise(compiler),
+            Ast::Return(r) => r.serialise(compiler),
+            Ast::Jump(j) => j.serialise(compiler)
+        };
+        serialised.map_err(|e| e.at(self.span()))
     }
 }
 
-fn c_ast_to_string(c_ast:&Vec<CommandAST>) -> String {
-    c_ast.iter().map(|t| t.serialise()).collect::<Vec<String>>().join("\n")
-}
-impl MCFunctionizable for CodeBlock {
-    fn mcfunctionate(&self, compiler:&mut Compiler) -> Result<MCFunction, CompileError> {
-        let mut serialised = Vec::new();
-        for ast in self.inside.iter() {
-            serialised.extend(ast.serialise(compiler)?);
+impl Compiler {
+    /// Serialises the commands, remembering the constants they refer to be set on load
+    fn c_ast_to_string(&mut self, c_ast:&[CommandAST]) -> String {
+        self.constants.extend(c_ast.iter().flat_map(|c| c.referred_constants()));
+        c_ast.iter().map(|t| t.serialise_in(&self.namespace)).collect::<Vec<String>>().join("\n")
+    }
+    /// Makes the function joining two strings by a function macro, unless it's already made
+    fn require_concat(&mut self) -> Result<(), CompileError> {
+        self.target.require(Feature::FunctionMacros, "joining strings", Span::default())?;
+        if self.compiled.iter().any(|f| f.get_full_path() == CONCAT_FUNCTION) {
+            return Ok(());
         }
-        let mut result = Vec::new();
-        for c_ast in serialised {
-            result.push(c_ast.serialise())
+        if let Some(span) = self.quoted_literal {
+            return Err(CompileErrorKind::UnjoinableString.at(span));
         }
+        let inside = arithmetic_operation::concat_macro()
+            .iter()
+            .map(|t| format!("${}", t.serialise_in(&self.namespace)))
+            .collect::<Vec<String>>()
+            .join("\n");
+        self.compiled.push(MCFunction {
+            name: CONCAT_FUNCTION.to_string(),
+            inside,
+            path: Vec::new(),
+            callment_prefix: String::new(),
+            preprocess: String::new(),
+            postprocess: String::new(),
+            returning_type: Type::None,
+            arguments: Vec::new(),
+            defined_at: Span::default(),
+            returns: false
+        });
+        Ok(())
+    }
+    /// The function creating the objectives and setting the constants used by the compiled ones
+    pub fn load_function(&self) -> MCFunction {
+        let mut commands = vec![CommandAST::Native(format!("scoreboard objectives add {} dummy", scoreboard::NAMESPACE))];
+        commands.extend(self.constants.iter().flat_map(|c| FormulaConstructer::new().set_constant(*c).build()));
+        MCFunction {
+            name: LOAD_FUNCTION_NAME.to_string(),
+            inside: commands.iter().map(|t| t.serialise_in(&self.namespace)).collect::<Vec<String>>().join("\n"),
+            path: Vec::new(),
+            callment_prefix: String::new(),
+            preprocess: String::new(),
+            postprocess: String::new(),
+            returning_type: Type::None,
+            arguments: Vec::new(),
+            defined_at: Span::default(),
+            returns: false
+        }
+    }
+}
+impl CodeBlock {
+    /// The variables defined inside are scoped by the name, and freed at the end of the block
+    pub fn compile_named(&self, name:String, compiler:&mut Compiler) -> Result<MCFunction, CompileError> {
+        let path = compiler.scope.clone();
+        let return_count = compiler.return_count;
+        compiler.scope.push(name.clone());
+        compiler.declare_functions(&self.inside)?;
+        let mut serialised = Self::serialise_statements(&self.inside, compiler)?;
+        serialised.extend(compiler.leave_current_scope());
+        compiler.scope.pop();
         Ok(MCFunction {
-            name: generate_random_id(32),
-            inside: result.join("\n"),
-            path: compiler.scope.clone(),
+            name,
+            inside: compiler.c_ast_to_string(&serialised),
+            path,
             callment_prefix: String::new(),
             preprocess: String::new(),
             postprocess: String::new(),
-            returning_type: Type::None
+            returning_type: Type::None,
+            arguments: Vec::new(),
+            defined_at: self.span,
+            returns: compiler.returns_since(return_count)
         })
     }
+    /// The statements after one which may `break` or `continue` are put into a function,
+    /// which is called only if none of the loops have been left
+    fn serialise_statements(statements:&[Ast], compiler:&mut Compiler) -> Result<Vec<CommandAST>, CompileError> {
+        let mut serialised = Vec::new();
+        for (i, ast) in statements.iter().enumerate() {
+            let mark = compiler.jumps.len();
+            serialised.extend(ast.serialise(compiler)?);
+            let jumps = compiler.jumps_since(mark);
+            let rest = &statements[i + 1..];
+            if jumps.is_empty() || rest.is_empty() {
+                continue;
+            }
+            let return_count = compiler.return_count;
+            let rest = Self::serialise_statements(rest, compiler)?;
+            let name = format!("after_{}", compiler.next_block_id());
+            let mut mcfunction = MCFunction::of_commands(name, &rest, compiler, ast.span());
+            mcfunction.returns = compiler.returns_since(return_count);
+            mcfunction.callment_prefix = jumps
+                .iter()
+                .fold(ExecuteConstructer::new(), |e, j| e.matches(j, false, "0".to_string()))
+                .build();
+            serialised.push(CommandAST::Native(mcfunction.call(compiler)));
+            compiler.compiled.push(mcfunction);
+            break;
+        }
+        Ok(serialised)
+    }
+}
+impl Compiler {
+    /// Whether a `return` compiled since the count has to be followed by the callers of the blocks
+    fn returns_since(&self, return_count:usize) -> bool {
+        self.target.supports(Feature::ReturnCommand) && self.return_count > return_count
+    }
+    /// The distinct control flags set since the mark
+    fn jumps_since(&self, mark:usize) -> Vec<Scoreboard> {
+        let mut jumps:Vec<Scoreboard> = Vec::new();
+        for jump in &self.jumps[mark..] {
+            if !jumps.iter().any(|j| j.get_mcname() == jump.get_mcname()) {
+                jumps.push(jump.clone());
+            }
+        }
+        jumps
+    }
+    /// Compiles the block holding the temporaries, which are freed by `return` inside it
+    fn compile_holding(&mut self, block:&CodeBlock, name:String, temps:&[Scoreboard]) -> Result<MCFunction, CompileError> {
+        let mark = self.held_temps.len();
+        self.held_temps.extend_from_slice(temps);
+        let compiled = block.compile_named(name, self);
+        self.held_temps.truncate(mark);
+        compiled
+    }
+    /// Compiles the body of a loop, which can be left by `break` and `continue` setting the control flag.
+    /// Returns the flags the body may set with whether it sets its own one,
+    /// and the flags of the outer loops are left for the statements after the loop
+    fn compile_loop(&mut self, block:&CodeBlock, name:String, label:&Option<String>, control:&Scoreboard, temp:&Scoreboard)
+        -> Result<(MCFunction, Vec<Scoreboard>, bool), CompileError> {
+        let mark = self.jumps.len();
+        self.loops.push((label.clone(), control.clone()));
+        let compiled = self.compile_holding(block, name, &[control.clone(), temp.clone()]);
+        self.loops.pop();
+        let mcfunction = compiled?;
+
+        let jumps = self.jumps_since(mark);
+        let is_controlled = jumps.iter().any(|j| j.get_mcname() == control.get_mcname());
+        let mut inner = self.jumps.split_off(mark);
+        inner.retain(|j| j.get_mcname() != control.get_mcname());
+        self.jumps.extend(inner);
+        Ok((mcfunction, jumps, is_controlled))
+    }
+}
+/// The control flag of a loop is 0 while it runs, and set to these by `continue` and `break`
+const CONTINUE_FLAG:i32 = 1;
+const BREAK_FLAG:i32 = 2;
+/// The commands of a loop controlled by `break` or `continue`:
+/// clearing the flag before the loop, clearing `continue` at the end of every iteration,
+/// and freeing the flag after the loop
+fn loop_control(compiler:&mut Compiler, control:&Scoreboard) -> (String, String, String) {
+    let clear = FormulaConstructer::new().assign_num(control, 0).build();
+    let clear = compiler.c_ast_to_string(&clear);
+    let next = format!(
+        "{}{}",
+        ExecuteConstructer::new().matches(control, false, CONTINUE_FLAG.to_string()).build(),
+        clear
+    );
+    let free = compiler.c_ast_to_string(&control.free());
+    (clear, next, free)
+}
+/// Joins the commands skipping the empty ones
+fn join_lines(lines:Vec<String>) -> String {
+    lines
+        .into_iter()
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+impl Serialisable for JumpStatement {
+    fn serialise(&self, compiler:&mut Compiler) -> Result<Vec<CommandAST>, CompileError> {
+        let keyword = if self.is_continue {"continue"} else {"break"};
+        let control = match &self.label {
+            Some(label) => compiler
+                .loops
+                .iter()
+                .rev()
+                .find(|(l, _)| l.as_ref() == Some(label))
+                .ok_or(CompileErrorKind::UndefinedLabel(label.clone()).at(self.span))?,
+            None => compiler
+                .loops
+                .last()
+                .ok_or(CompileErrorKind::JumpOutsideOfLoop(keyword.to_string()).at(self.span))?
+        }.1.clone();
+        compiler.jumps.push(control.clone());
+        Ok(FormulaConstructer::new()
+            .assign_num(&control, if self.is_continue {CONTINUE_FLAG} else {BREAK_FLAG})
+            .build())
+    }
+}
+impl Compiler {
+    /// A function macro puts a string into the quotes of the joined one as it is, which `"` or `\` would break.
+    /// Every string is made of literals, so none of them can have those once strings are joined.
+    fn check_joinable(&mut self, literal:&str, span:Span) -> Result<(), CompileError> {
+        if !literal.contains(['"', '\\']) || self.quoted_literal.is_some() {
+            return Ok(());
+        }
+        if self.compiled.iter().any(|f| f.get_full_path() == CONCAT_FUNCTION) {
+            return Err(CompileErrorKind::UnjoinableString.at(span));
+        }
+        self.quoted_literal = Some(span);
+        Ok(())
+    }
+    /// Every call of a function shares its scores, so a call which may come back to the caller
+    /// has to be the whole value of `return`, after which the caller uses none of them
+    pub fn check_recursion(&self) -> Result<(), CompileError> {
+        let reaches = |from:&str, to:&str| {
+            let mut visited = vec![from];
+            let mut stack = vec![from];
+            while let Some(caller) = stack.pop() {
+                for (_, callee, _, _) in self.calls.iter().filter(|c| c.0 == caller) {
+                    if callee == to {
+                        return true;
+                    }
+                    if !visited.contains(&callee.as_str()) {
+                        visited.push(callee);
+                        stack.push(callee);
+                    }
+                }
+            }
+            false
+        };
+        match self.calls.iter().find(|(caller, callee, tail, _)| !tail && (caller == callee || reaches(callee, caller))) {
+            Some((_, callee, _, span)) => Err(CompileErrorKind::NonTailRecursion(callee.clone()).at(*span)),
+            None => Ok(())
+        }
+    }
+    /// Makes the functions defined in the block callable before their bodies are compiled,
+    /// so that they can call themselves or the ones defined later
+    fn declare_functions(&mut self, statements:&[Ast]) -> Result<(), CompileError> {
+        let definitions = statements.iter().filter_map(|s| match s {
+            Ast::FunctionDefinement(f) => Some(f),
+            _ => None
+        });
+        for definition in definitions {
+            // `main` and the functions starting with `__` are made by the compiler at the root
+            if definition.func_name == "main" || definition.func_name.starts_with("__") {
+                return Err(CompileErrorKind::ReservedFunctionName(definition.func_name.clone()).at(definition.span));
+            }
+            if let Some(previous) = self.get_func(&definition.func_name) {
+                return Err(CompileErrorKind::FunctionRedefined(definition.func_name.clone(), previous.defined_at).at(definition.span));
+            }
+            self.functions.push(definition.signature());
+        }
+        Ok(())
+    }
+}
+impl MCFunctionizable for CodeBlock {
+    fn mcfunctionate(&self, compiler:&mut Compiler) -> Result<MCFunction, CompileError> {
+        let name = format!("block_{}", compiler.next_block_id());
+        self.compile_named(name, compiler)
+    }
 }
 impl MCFunctionizable for IfSyntax {
     fn mcfunctionate(&self, compiler:&mut Compiler) -> Result<MCFunction, CompileError> {
-        let mut mcfunction = self.block.mcfunctionate(compiler)?;
-        let condition_reserv = self.condition.to_calc_reserv(compiler)?;
-        let is_true = Scoreboard {
-            name: format!("IF_CONDITION_{}", scoreboard::generate_random_id(32)),
+        let id = compiler.next_block_id();
+        let condition = Scoreboard {
+            name: format!("IF_CONDITION_{}", id),
             scope: compiler.scope.clone(),
-            datatype: condition_reserv.guess_type()?
-        };
-        let zero_const = Scoreboard {
-            name: format!("0"),
-            scope: vec!["CONST".to_string()],
-            datatype: Type::Int
+            datatype: Type::Bool
         };
+        let held = [condition.clone()];
+        let mut mcfunction = compiler.compile_holding(&self.block, format!("if_{}", id), &held)?;
+        let condition_reserv = self.condition.to_calc_reserv(compiler)?;
+        // The condition is captured once, so that a branch can't trigger the following ones
+        let is_true = Scoreboard { datatype: condition_reserv.guess_type()?, ..condition };
 
-        let mut preprocess = condition_reserv.to_be(&is_true)?;
-        preprocess.extend(
-            FormulaConstructer
-                ::new()
-                .assign_num(&zero_const, 0)
-                .build()
-        );
-        mcfunction.preprocess = c_ast_to_string(&preprocess);
+        let eval = condition_reserv.to_be(compiler, &is_true)?;
+        mcfunction.preprocess = compiler.c_ast_to_string(&eval);
         mcfunction.callment_prefix = ExecuteConstructer
             ::new()
-            .compare(
-                &is_true,
-                &Comparison::Neq,
-                &zero_const
-            ).build();
-        mcfunction.postprocess = c_ast_to_string(
-            &FormulaConstructer
+            .matches(&is_true, true, "0".to_string())
+            .build();
+        let mut postprocess = Vec::new();
+        if let Some(else_block) = &self.else_block {
+            let mut else_mcfunction = compiler.compile_holding(else_block, format!("else_{}", id), &held)?;
+            else_mcfunction.callment_prefix = ExecuteConstructer
                 ::new()
-                .free(&is_true)
-                .free(&zero_const)
-                .build()
-        );
+                .matches(&is_true, false, "0".to_string())
+                .build();
+            postprocess.push(CommandAST::Native(else_mcfunction.call(compiler)));
+            compiler.compiled.push(else_mcfunction);
+        }
+        postprocess.extend(is_true.free());
+        mcfunction.postprocess = compiler.c_ast_to_string(&postprocess);
         Ok(mcfunction)
     }
 }
 impl MCFunctionizable for WhileSyntax {
     fn mcfunctionate(&self, compiler:&mut Compiler) -> Result<MCFunction, CompileError> {
-        let mut codeblock = self.block.mcfunctionate(compiler)?;
+        let id = compiler.next_block_id();
+        let control = Scoreboard {
+            name: format!("LOOP_CONTROL_{}", id),
+            scope: compiler.scope.clone(),
+            datatype: Type::Int
+        };
         let is_true = Scoreboard {
-            name: format!("WHILE_CONDITION_{}", scoreboard::generate_random_id(32)),
+            name: format!("WHILE_CONDITION_{}", id),
             scope: compiler.scope.clone(),
             datatype: Type::Bool
         };
-        let zero_const = Scoreboard {
-            name: format!("0"),
-            scope: vec!["CONST".to_string()],
-            datatype: Type::Int
-        };
-        
-        let mut eval = self
+        let (mut codeblock, jumps, is_controlled) = compiler.compile_loop(
+            &self.block, format!("while_{}", id), &self.label, &control, &is_true
+        )?;
+
+        let eval = self
             .condition
             .to_calc_reserv(compiler)?
-            .to_be(&is_true)?;
-        eval.extend(FormulaConstructer
-            ::new()
-            .assign_num(&zero_const, 0)
-            .build()
-        );
-        eval.push(CommandAST::Native(format!(
-            "{} {}",
-            ExecuteConstructer::new()
-                .compare(&is_true,&Comparison::Neq,&zero_const).build(),
-            codeblock.call(compiler)
-        )));
-        eval.extend(FormulaConstructer
-            ::new()
-            .free(&is_true)
-            .free(&zero_const)
-            .build()
-        );
-        codeblock.postprocess = c_ast_to_string(&eval);
+            .to_be(compiler, &is_true)?;
+        let free = is_true.free();
+        // It doesn't repeat after any loop has been left
+        codeblock.callment_prefix = jumps
+            .iter()
+            .fold(ExecuteConstructer::new(), |e, j| e.matches(j, false, "0".to_string()))
+            .matches(&is_true, true, "0".to_string())
+            .build();
+        let eval = compiler.c_ast_to_string(&eval);
+        let free = compiler.c_ast_to_string(&free);
+        let (clear, next, free_control) = if is_controlled {
+            loop_control(compiler, &control)
+        } else {
+            Default::default()
+        };
+        // The body re-evaluates the condition and calls itself again at its end
+        let recursion = join_lines(vec![
+            next,
+            eval.clone(),
+            codeblock.callment(compiler),
+            free.clone()
+        ]);
+        codeblock.inside = join_lines(vec![codeblock.inside.clone(), recursion]);
+        codeblock.preprocess = join_lines(vec![clear, eval]);
+        codeblock.postprocess = join_lines(vec![free, free_control]);
         Ok(codeblock)
     }
 }
 
+impl MCFunctionizable for ForSyntax {
+    fn mcfunctionate(&self, compiler:&mut Compiler) -> Result<MCFunction, CompileError> {
+        let id = compiler.next_block_id();
+        let name = format!("for_{}", id);
+        // The counter has its own scope, so that it isn't freed at the end of every iteration
+        let mut counter_scope = compiler.scope.clone();
+        counter_scope.push(format!("FOR_{}", id));
+        let counter = Scoreboard { name: self.variable.clone(), scope: counter_scope, datatype: Type::Int };
+        let end = Scoreboard {
+            name: format!("FOR_END_{}", id),
+            scope: compiler.scope.clone(),
+            datatype: Type::Int
+        };
+        // The range is evaluated once before the loop, where the counter isn't defined yet
+        let mut eval = self.start.to_calc_reserv(compiler)?.serialise(compiler, &counter)?;
+        eval.extend(self.end.to_calc_reserv(compiler)?.serialise(compiler, &end)?);
+
+        let control = Scoreboard {
+            name: format!("LOOP_CONTROL_{}", id),
+            scope: compiler.scope.clone(),
+            datatype: Type::Int
+        };
+
+        compiler.declarations.insert(counter.get_mcname(), self.span);
+        compiler.variables.push(counter.clone());
+        let compiled = compiler.compile_loop(&self.block, name, &self.label, &control, &end);
+        compiler.variables.retain(|v| v.get_mcname() != counter.get_mcname());
+        let (mut codeblock, jumps, is_controlled) = compiled?;
+
+        let comparison = match (self.step > 0, self.inclusive) {
+            (true, false) => Comparison::Lt,
+            (true, true) => Comparison::Le,
+            (false, false) => Comparison::Gt,
+            (false, true) => Comparison::Ge
+        };
+        // It doesn't repeat after any loop has been left
+        codeblock.callment_prefix = jumps
+            .iter()
+            .fold(ExecuteConstructer::new(), |e, j| e.matches(j, false, "0".to_string()))
+            .compare(&counter, &comparison, &end)
+            .build();
+        let step = FormulaConstructer::new()
+            .add_rem_num(&counter, "add".to_string(), self.step)
+            .build();
+        let mut free = counter.free();
+        free.extend(end.free());
+        let (clear, next, free_control) = if is_controlled {
+            loop_control(compiler, &control)
+        } else {
+            Default::default()
+        };
+        // The body steps the counter and calls itself again at its end while it's in the range
+        let recursion = join_lines(vec![
+            next,
+            compiler.c_ast_to_string(&step),
+            codeblock.callment(compiler)
+        ]);
+        codeblock.inside = join_lines(vec![codeblock.inside.clone(), recursion]);
+        codeblock.preprocess = join_lines(vec![clear, compiler.c_ast_to_string(&eval)]);
+        codeblock.postprocess = join_lines(vec![compiler.c_ast_to_string(&free), free_control]);
+        Ok(codeblock)
+    }
+}
+
+#[test]
+fn function_test() {
+    let compiled = crate::compile(
+        "fn f(a:int, b:float) -> int { return a * 2; } let x = f(1, 2.5) * 3;"
+    ).unwrap();
+    let f = compiled.iter().find(|f| f.name == "f").unwrap();
+    assert_eq!(f.returning_type, Type::Int);
+    assert!(f.inside.contains("scoreboard players operation #f.RETURN MCPP.var = "));
+    let main = &compiled[0].inside;
+    assert!(main.contains("scoreboard players operation #f.b MCPP.var = "));
+    assert!(main.contains("function MCPP:f\nscoreboard players operation #TEMP.CALC_RESULT_"));
+    assert!(crate::compile("fn f(a:int) {} f(1, 2);").is_err());
+}
+#[test]
+fn declaration_test() {
+    // A function can be called by itself and before it's defined
+    let source = "
+        let a = sum(10, 0);
+        fn sum(n:int, acc:int) -> int { if n == 0 { return acc; } return sum(n - 1, acc + n); }
+    ";
+    for target in ["1.20.3", "1.20.2"] {
+        let mut compiler = Compiler::from("MCPP");
+        compiler.target = target.parse().unwrap();
+        let compiled = crate::compile_with(compiler, source).unwrap();
+        let mut vm = crate::vm::VirtualMachine::new().functions("MCPP", &compiled);
+        vm.run("MCPP:__load").unwrap();
+        vm.run("MCPP:main").unwrap();
+        assert_eq!(vm.last_score("#main.a", "MCPP.var"), Some(55));
+    }
+    assert!(matches!(
+        crate::compile("fn f() {}\nfn f(a:int) {}"),
+        Err(Spanned { inner: CompileErrorKind::FunctionRedefined(n, p), span: s }) if n == "f" && s.line == 2 && p.line == 1
+    ));
+    assert!(matches!(
+        crate::compile("if true { fn f() {} } fn f() {}"),
+        Err(Spanned { inner: CompileErrorKind::FunctionRedefined(_, _), .. })
+    ));
+    for reserved in ["main", "__load", "__concat"] {
+        assert!(matches!(
+            crate::compile(&format!("fn {}() {{}}", reserved)),
+            Err(Spanned { inner: CompileErrorKind::ReservedFunctionName(n), .. }) if n == reserved
+        ));
+    }
+}
+#[test]
+fn recursion_test() {
+    // Every call shares the scores of the function, so only the value of `return` can recurse
+    let source = "
+        fn fact(n:int, acc:int) -> int { if n <= 1 { return acc; } return fact(n - 1, acc * n); }
+        fn twice(n:int) -> int { return double(double(n)); }
+        fn double(n:int) -> int { return n * 2; }
+        let a = fact(5, 1);
+        let b = twice(3);
+    ";
+    for target in ["1.20.3", "1.20.2"] {
+        let mut compiler = Compiler::from("MCPP");
+        compiler.target = target.parse().unwrap();
+        let compiled = crate::compile_with(compiler, source).unwrap();
+        let mut vm = crate::vm::VirtualMachine::new().functions("MCPP", &compiled);
+        vm.run("MCPP:__load").unwrap();
+        vm.run("MCPP:main").unwrap();
+        assert_eq!(vm.last_score("#main.a", "MCPP.var"), Some(120));
+        assert_eq!(vm.last_score("#main.b", "MCPP.var"), Some(12));
+    }
+    let non_tail = [
+        ("fn fact(n:int) -> int { if n <= 1 { return 1; } return n * fact(n - 1); }\nlet a = fact(5);", "fact", 60),
+        ("fn f(n:int) -> int { let m = f(n - 1); return m; }", "f", 30),
+        ("fn f(n:int) -> int { return f(f(n)); }", "f", 31),
+        ("fn even(n:int) -> int { return 1 - odd(n); }\nfn odd(n:int) -> int { return even(n - 1); }", "odd", 36)
+    ];
+    for (source, callee, column) in non_tail {
+        assert!(matches!(
+            crate::compile(source),
+            Err(Spanned { inner: CompileErrorKind::NonTailRecursion(n), span: s }) if n == callee && s.line == 1 && s.column == column
+        ), "{}", source);
+    }
+}
+#[test]
+fn missing_return_test() {
+    // Every way through a function returning a value ends with `return`
+    for source in [
+        "fn f(x:int) -> int { if x < 0 { return 0; } else { return x; } }",
+        "fn f(x:int) -> int { { return x; } }",
+        "fn f(x:int) -> int { while true { if x > 9 { return x; } x = x + 1; } }",
+        "fn f(x:int) -> int { 'outer: while true { while true { break; } return x; } }",
+        "fn f(x:int) { if x < 0 { return; } }"
+    ] {
+        assert!(crate::compile(source).is_ok(), "{}", source);
+    }
+    for source in [
+        "fn f(x:int) -> int { if x < 0 { return 0; } }",
+        "fn f(x:int) -> int { if x < 0 { return 0; } else if x == 0 { return 1; } }",
+        "fn f(x:int) -> int { while x < 9 { return x; } }",
+        "fn f(x:int) -> int { while true { if x > 9 { break; } return x; } }",
+        "fn f(x:int) -> int { 'outer: while true { while true { break 'outer; } return x; } }",
+        "fn f(x:int) -> int { for i in 0..x { return i; } }"
+    ] {
+        assert!(matches!(
+            crate::compile(source),
+            Err(Spanned { inner: CompileErrorKind::MissingReturn(n, Type::Int), span: s }) if n == "f" && s.column == 1
+        ), "{}", source);
+    }
+    assert!(matches!(
+        crate::compile("fn f(x:int) -> int { if x < 0 { return; } return x; }"),
+        Err(Spanned { inner: CompileErrorKind::MissingReturnValue(Type::Int), span: s }) if s.column == 33
+    ));
+}
+#[test]
+fn else_test() {
+    let compiled = crate::compile(
+        "let a = 5; if a > 3 { a = 1; } else if a == 1 { a = 2; } else { a = 3; }"
+    ).unwrap();
+    // main, then, else (the inner if), its then, its else and load
+    assert_eq!(compiled.len(), 6);
+    let main = &compiled[0].inside;
+    let is_true = main
+        .split_whitespace()
+        .find(|w| w.contains("IF_CONDITION_"))
+        .unwrap();
+    let then_call = main.find(&format!("execute unless score {} MCPP.var matches 0 run function", is_true));
+    let else_call = main.find(&format!("execute if score {} MCPP.var matches 0 run function", is_true));
+    assert!(then_call.unwrap() < else_call.unwrap());
+}
+#[test]
+fn error_span_test() {
+    let error = crate::compile("let a = 1;\nlet b = a + c;").unwrap_err();
+    assert!(matches!(error, Spanned { inner: CompileErrorKind::UndefinedIdentifierReferenced(_), .. }));
+    assert_eq!((error.span.line, error.span.column), (2, 13));
+    let error = crate::compile("fn f() {}\nlet a = 1 + true;").unwrap_err();
+    assert_eq!((error.span.line, error.span.column), (2, 11));
+    let error = crate::compile("let a = 1;\n\n  return a;").unwrap_err();
+    assert_eq!((error.span.line, error.span.column), (3, 3));
+}
+#[test]
+fn load_test() {
+    let compiled = crate::compile("let a = 1.5; let b = a * 2.0; if b > 1.0 { b = 0.0; }").unwrap();
+    let load = compiled.last().unwrap();
+    assert_eq!(load.name, LOAD_FUNCTION_NAME);
+    assert!(load.inside.starts_with("scoreboard objectives add MCPP.var dummy\n"));
+    // Every constant is set once on load, and never reset
+    for constant in ["0", "1000"] {
+        assert!(load.inside.contains(&format!("scoreboard players set #CONSTANT.{0} MCPP.var {0}", constant)));
+    }
+    for mcfunction in &compiled[..compiled.len() - 1] {
+        assert!(!mcfunction.inside.contains("players set #CONSTANT."));
+        assert!(!mcfunction.inside.contains("players reset #CONSTANT."));
+    }
+}
+
+#[test]
+fn evaluate_test() {
+    let evaluated = Compiler::from("MCPP").evaluate("{ let a = 0; while a < 3 { a = a + 1; } }".to_string()).unwrap();
+    assert!(evaluated.starts_with("scoreboard objectives add MCPP.var dummy\n"));
+    // The functions the main calls are given with it
+    assert!(evaluated.contains("function MCPP:main/while_0"));
+    assert!(evaluated.contains("\n\n# MCPP:main/while_0\n"));
+}
+#[test]
+fn unary_test() {
+    let compiled = crate::compile("let a = 2; let b = -a; let c = !(a > 1); let d = -1.5 + +a;").unwrap();
+    assert!(compiled[0].inside.contains("*= #CONSTANT.-1 MCPP.var"));
+    assert!(compiled[0].inside.contains("scoreboard players set #TEMP.CALC_RESULT_2 MCPP.var -1500"));
+    assert!(matches!(
+        crate::compile("let a = !1;"),
+        Err(Spanned { inner: CompileErrorKind::UndefinedUnaryOperation(Oper::Unary(_), Type::Int), .. })
+    ));
+    assert!(matches!(
+        crate::compile("let a = 1 !;"),
+        Err(Spanned { inner: CompileErrorKind::ASyntaxErrorOccured(SyntaxError::OperatorAtInvalidPosition(_, _)), .. })
+    ));
+}
+#[test]
+fn naming_test() {
+    let source = "let a = 1; if a > 0 { a = 2; } else { a = 3; } while a < 5 { if a == 4 { a = 5; } a = a + 1; }";
+    let compiled = crate::compile(source).unwrap();
+    let paths = compiled.iter().map(|f| f.get_full_path()).collect::<Vec<String>>();
+    assert_eq!(paths, ["main", "main/else_0", "main/if_0", "main/while_1/if_0", "main/while_1", "__load"]);
+    assert!(compiled[0].inside.contains("#main.IF_CONDITION_0"));
+    // The same source always compiles to the same output
+    let again = crate::compile(source).unwrap();
+    assert!(compiled.iter().zip(again.iter()).all(|(a, b)| a.inside == b.inside));
+}
+#[test]
+fn short_circuit_test() {
+    let run = |source:&str| {
+        let compiled = crate::compile(&format!("fn touch() -> bool {{ let touched = 1; return true; }} {}", source)).unwrap();
+        let mut vm = crate::vm::VirtualMachine::new().functions("MCPP", &compiled);
+        vm.run("MCPP:__load").unwrap();
+        vm.run("MCPP:main").unwrap();
+        (vm.last_score("#main.a", "MCPP.var"), vm.last_score("#touch.touched", "MCPP.var"))
+    };
+    // The right side isn't called once the left side decides the result
+    assert_eq!(run("let a = false & touch();"), (Some(0), None));
+    assert_eq!(run("let a = true | touch();"), (Some(1), None));
+    assert_eq!(run("let a = true & touch();"), (Some(1), Some(1)));
+    assert_eq!(run("let a = 1 > 2 | touch() & touch();"), (Some(1), Some(1)));
+    let compiled = crate::compile("let b = true; let a = b & 1 < 2;").unwrap();
+    assert!(compiled.iter().any(|f| f.get_full_path() == "main/and_0"));
+    assert!(compiled[0].inside.contains("matches 1 run function MCPP:main/and_0"));
+}
+#[test]
+fn native_test() {
+    let compiled = crate::compile(r#"
+        native!("scoreboard players set #x MCPP.var 5");
+        let a = native!(int, "scoreboard players get #x MCPP.var") * 2;
+        let b:float = native!(float, "scoreboard players get #x MCPP.var") / 2;
+        let c = native!(bool, "scoreboard players get #x MCPP.var") & true;
+    "#).unwrap();
+    assert!(compiled[0].inside.starts_with("scoreboard players set #x MCPP.var 5\n"));
+    let mut vm = crate::vm::VirtualMachine::new().functions("MCPP", &compiled);
+    vm.run("MCPP:__load").unwrap();
+    vm.run("MCPP:main").unwrap();
+    assert_eq!(vm.last_score("#main.a", "MCPP.var"), Some(10));
+    assert_eq!(vm.last_score("#main.b", "MCPP.var"), Some(2500));
+    assert_eq!(vm.last_score("#main.c", "MCPP.var"), Some(1));
+    assert!(crate::compile(r#"let a = native!(1, "say hi");"#).is_err());
+    assert!(crate::compile(r#"native!(int, "say hi", "say hi");"#).is_err());
+    assert!(crate::compile("let a = unknown!(1);").is_err());
+}
+#[test]
+fn macro_test() {
+    /// `twice!(value)`, which doubles the value
+    struct Twice;
+    impl macros::Macro for Twice {
+        fn returning_type(&self, arguments:&Tuple, compiler:&Compiler) -> Result<Type, CompileError> {
+            arguments.type_of(0, compiler)
+        }
+        fn expand(&self, arguments:&Tuple, compiler:&mut Compiler, container:&Scoreboard) -> Result<Vec<CommandAST>, CompileError> {
+            let mut commands = arguments.evaluate(0, compiler, container)?;
+            commands.extend(FormulaConstructer::new().calc_score(container, "+=".to_string(), container).build());
+            Ok(commands)
+        }
+    }
+    let mut compiler = Compiler::from("MCPP");
+    compiler.register_macro("twice", Twice);
+    let compiled = crate::compile_with(compiler, "let a = 3; let b = twice!(a + 1) * 2; let c = twice!(1.5);").unwrap();
+    let mut vm = crate::vm::VirtualMachine::new().functions("MCPP", &compiled);
+    vm.run("MCPP:__load").unwrap();
+    vm.run("MCPP:main").unwrap();
+    assert_eq!(vm.last_score("#main.b", "MCPP.var"), Some(16));
+    assert_eq!(vm.last_score("#main.c", "MCPP.var"), Some(3000));
+    // A macro isn't shared between compilers
+    assert!(crate::compile("let b = twice!(1);").is_err());
+}
+#[test]
+fn string_test() {
+    let compiled = crate::compile(r#"
+        let name:str = "Steve";
+        let greet = "Hi, " + name + "!";
+        let same = greet == "Hi, Steve!";
+        let diff = name != "Steve";
+        let other:str = "Alex";
+        other = name;
+        let copied = other == name;
+        native!("scoreboard players set #{name} MCPP.var 7");
+    "#).unwrap();
+    assert!(compiled[0].inside.starts_with("data modify storage MCPP:vars main.name set value \"Steve\"\n"));
+    assert_eq!(compiled.iter().filter(|f| f.get_full_path() == "__concat").count(), 1);
+    let mut vm = crate::vm::VirtualMachine::new().functions("MCPP", &compiled);
+    vm.run("MCPP:__load").unwrap();
+    vm.run("MCPP:main").unwrap();
+    assert_eq!(vm.last_score("#main.same", "MCPP.var"), Some(1));
+    assert_eq!(vm.last_score("#main.diff", "MCPP.var"), Some(0));
+    assert_eq!(vm.last_score("#main.copied", "MCPP.var"), Some(1));
+    assert_eq!(vm.score("#Steve", "MCPP.var"), Some(7));
+    // The strings are removed on leaving their scope as the scores are
+    assert_eq!(vm.data("MCPP:vars", "main.name"), None);
+    assert!(crate::compile(r#"let a = "x" < "y";"#).is_err());
+    assert!(crate::compile(r#"let a = "x" + 1;"#).is_err());
+    assert!(crate::compile(r#"let a:int = "x";"#).is_err());
+    // A quote or a backslash would break the quotes the joined string is put into
+    assert!(crate::compile(r#"let a = "C:\mcpp"; let b = a == "\";"#).is_ok());
+    for source in [r#"let a = "\" + "b";"#, r#"let a = "x" + "b"; let c = "a\b";"#] {
+        assert!(matches!(
+            crate::compile(source),
+            Err(Spanned { inner: CompileErrorKind::UnjoinableString, span: s }) if source[s.start..s.end].contains(['"', '\\'])
+        ), "{}", source);
+    }
+}
+#[test]
+fn template_test() {
+    let compiled = crate::compile(r#"
+        let count = 3;
+        let rate = 2.5;
+        let name:str = "x";
+        native!("scoreboard players set #{name} MCPP.var {count}");
+        let added = native!(int, "scoreboard players add #{name} MCPP.var {count}");
+        native!("data modify storage MCPP:vars rate set value {rate}");
+    "#).unwrap();
+    let template = compiled.iter().find(|f| f.get_full_path() == "main/native_0").unwrap();
+    assert_eq!(template.inside, "$scoreboard players set #$(name) MCPP.var $(count)");
+    assert!(compiled[0].inside.contains(
+        "execute store result storage MCPP:vars TEMP.NATIVE.count int 1 run scoreboard players get #main.count MCPP.var\n"
+    ));
+    let mut vm = crate::vm::VirtualMachine::new().functions("MCPP", &compiled);
+    vm.run("MCPP:__load").unwrap();
+    vm.run("MCPP:main").unwrap();
+    assert_eq!(vm.score("#x", "MCPP.var"), Some(6));
+    assert_eq!(vm.last_score("#main.added", "MCPP.var"), Some(6));
+    assert_eq!(vm.data("MCPP:vars", "rate"), Some(&crate::vm::Nbt::Double(2.5)));
+    assert!(crate::compile(r#"native!("say {undefined}");"#).is_err());
+}
+
+#[test]
+fn precision_test() {
+    let compiled = crate::compile(r#"
+        let coarse:float<2> = 1.25;
+        let fine:fixed<4> = 0.5025;
+        let sum = coarse + fine;
+        let product = coarse * fine;
+        let quotient = fine / coarse;
+        let less = coarse < fine + 0.75;
+        let greater = fine > coarse;
+        let moved:float<1> = fine + 1;
+        let floored:int = fine * 3;
+    "#).unwrap();
+    let mut vm = crate::vm::VirtualMachine::new().functions("MCPP", &compiled);
+    vm.run("MCPP:__load").unwrap();
+    vm.run("MCPP:main").unwrap();
+    assert_eq!(vm.last_score("#main.sum", "MCPP.var"), Some(175));
+    assert_eq!(vm.last_score("#main.product", "MCPP.var"), Some(62));
+    assert_eq!(vm.last_score("#main.quotient", "MCPP.var"), Some(4020));
+    assert_eq!(vm.last_score("#main.less", "MCPP.var"), Some(1));
+    assert_eq!(vm.last_score("#main.greater", "MCPP.var"), Some(0));
+    assert_eq!(vm.last_score("#main.moved", "MCPP.var"), Some(15));
+    assert_eq!(vm.last_score("#main.floored", "MCPP.var"), Some(1));
+    // A literal which doesn't fit in a score at the decimal places
+    assert!(matches!(
+        crate::compile("let big:float<6> = 5000.0;"),
+        Err(Spanned { inner: CompileErrorKind::FloatOutOfRange(_, Type::Float(6)), .. })
+    ));
+    assert!(matches!(
+        crate::compile("let big:float<2> = 99999999.0;"),
+        Err(Spanned { inner: CompileErrorKind::FloatOutOfRange(v, _), .. }) if v == "99999999.0"
+    ));
+    assert!(crate::compile("let a:float<10> = 1.0;").is_err());
+}
+
+#[test]
+fn cast_test() {
+    let source = "
+        let speed = 2.75;
+        let whole = speed as int;
+        let coarse = speed as float<1>;
+        let fine = 0.12345 as float<5>;
+        let moving = speed as bool;
+        let flag = (3 > 1) as int * 5;
+    ";
+    let compiled = crate::compile(source).unwrap();
+    let mut vm = crate::vm::VirtualMachine::new().functions("MCPP", &compiled);
+    vm.run("MCPP:__load").unwrap();
+    vm.run("MCPP:main").unwrap();
+    assert_eq!(vm.last_score("#main.whole", "MCPP.var"), Some(2));
+    assert_eq!(vm.last_score("#main.coarse", "MCPP.var"), Some(27));
+    assert_eq!(vm.last_score("#main.fine", "MCPP.var"), Some(12345));
+    assert_eq!(vm.last_score("#main.moving", "MCPP.var"), Some(1));
+    assert_eq!(vm.last_score("#main.flag", "MCPP.var"), Some(5));
+    assert!(matches!(
+        crate::compile(r#"let s:str = "1"; let i = s as int;"#),
+        Err(Spanned { inner: CompileErrorKind::UndefinedUnaryOperation(_, Type::Str), .. })
+    ));
+    // Dropping digits has to be written explicitly in the strict mode
+    let strict = |source:&str| {
+        let mut compiler = Compiler::from("MCPP");
+        compiler.strict = true;
+        crate::compile_with(compiler, source)
+    };
+    assert!(strict(source).is_ok());
+    assert!(matches!(
+        strict("let a = 2.5; let b:int = a;"),
+        Err(Spanned { inner: CompileErrorKind::ImplicitNarrowing(Type::Float(3), Type::Int), .. })
+    ));
+    assert!(strict("let a:float<2> = 2.5; let b:fixed<4> = a; let c:float<2> = b;").is_err());
+    assert!(strict("let a:int = 2.0;").is_err());
+    assert!(strict("let a:float<2> = 0.5; let b:float = 1;").is_ok());
+    // The right side of an arithmetic is narrowed into the type of the left side
+    assert!(matches!(
+        strict("let a:int = 1;\na = a + 2.5;"),
+        Err(Spanned { inner: CompileErrorKind::ImplicitNarrowing(Type::Float(3), Type::Int), span: s }) if s.line == 2
+    ));
+    assert!(strict("let a:float<2> = 1.5; let b:fixed<4> = 0.25; let c = a * b;").is_err());
+    assert!(strict("let a:float<2> = 1.5; let b:float<2> = a * 2 + 0.25;").is_ok());
+    assert!(strict("let a:int = 1; a = (a as float + 2.5) as int;").is_ok());
+}
+
+#[test]
+fn for_test() {
+    let compiled = crate::compile("
+        let total = 0;
+        let i = 100;
+        for i in 0..4 { let square = i * i; total = total + square; }
+        let last = 0;
+        for j in i..=i + 10 step 5 { last = j; }
+        let down = 0;
+        for k in 3..0 step -1 { down = down * 10 + k; }
+        for never in 5..5 { total = 0; }
+    ").unwrap();
+    let mut vm = crate::vm::VirtualMachine::new().functions("MCPP", &compiled);
+    vm.run("MCPP:__load").unwrap();
+    vm.run("MCPP:main").unwrap();
+    assert_eq!(vm.last_score("#main.total", "MCPP.var"), Some(14));
+    // The range refers the outer `i`, since the counter is only visible inside the body
+    assert_eq!(vm.last_score("#main.last", "MCPP.var"), Some(110));
+    assert_eq!(vm.last_score("#main.down", "MCPP.var"), Some(321));
+    assert!(crate::compile("for i in 0..3 {} let a = i;").is_err());
+    assert!(matches!(
+        crate::compile("for i in 0..3 step 0 {}"),
+        Err(Spanned { inner: CompileErrorKind::ASyntaxErrorOccured(SyntaxError::ExpectedAToken(_, _)), .. })
+    ));
+}
+#[test]
+fn jump_test() {
+    let compiled = crate::compile("
+        let odd = 0;
+        let n = 0;
+        while true {
+            n = n + 1;
+            if n > 9 { break; }
+            if n % 2 == 0 { continue; }
+            odd = odd + n;
+        }
+        let pairs = 0;
+        'outer: for i in 0..5 {
+            for j in 0..5 {
+                if j > i { continue 'outer; }
+                if i == 3 { break 'outer; }
+                pairs = pairs + 1;
+            }
+        }
+        let after = 0;
+        for i in 0..3 { break; }
+        after = 1;
+    ").unwrap();
+    let mut vm = crate::vm::VirtualMachine::new().functions("MCPP", &compiled);
+    vm.run("MCPP:__load").unwrap();
+    vm.run("MCPP:main").unwrap();
+    assert_eq!(vm.last_score("#main.odd", "MCPP.var"), Some(25));
+    assert_eq!(vm.last_score("#main.n", "MCPP.var"), Some(10));
+    // (0, 0), (1, 0), (1, 1), (2, 0), (2, 1) and (2, 2)
+    assert_eq!(vm.last_score("#main.pairs", "MCPP.var"), Some(6));
+    // Leaving a loop doesn't skip the statements after it
+    assert_eq!(vm.last_score("#main.after", "MCPP.var"), Some(1));
+    assert!(matches!(crate::compile("break;"), Err(Spanned { inner: CompileErrorKind::JumpOutsideOfLoop(_), .. })));
+    assert!(matches!(
+        crate::compile("while true { fn f() { continue; } }"),
+        Err(Spanned { inner: CompileErrorKind::JumpOutsideOfLoop(_), .. })
+    ));
+    assert!(matches!(
+        crate::compile("'a: while true { break 'b; }"),
+        Err(Spanned { inner: CompileErrorKind::UndefinedLabel(_), .. })
+    ));
+}
+#[test]
+fn early_return_test() {
+    let source = "
+        fn root(n:int) -> int {
+            let i = 0;
+            while true {
+                if i * i >= n { return i; }
+                i = i + 1;
+            }
+            return -1;
+        }
+        fn sign(x:int) -> int { if x < 0 { return -1; } else if x == 0 { return 0; } return 1; }
+        fn third(n:int) -> int { for k in 0..n { if k * 3 >= n { return k; } } return -1; }
+        let a = root(20);
+        let b = sign(-3) * 100 + sign(0) * 10 + sign(7);
+        let c = third(10);
+    ";
+    // The `return` command, and the flag for the targets without it
+    for (target, return_command) in [("1.20.3", true), ("1.20.2", false)] {
+        let mut compiler = Compiler::from("MCPP");
+        compiler.target = target.parse().unwrap();
+        let compiled = crate::compile_with(compiler, source).unwrap();
+        let sign = compiled.iter().find(|f| f.name == "sign").unwrap();
+        assert_eq!(sign.inside.contains("return 1"), return_command);
+        let mut vm = crate::vm::VirtualMachine::new().functions("MCPP", &compiled);
+        vm.run("MCPP:__load").unwrap();
+        vm.run("MCPP:main").unwrap();
+        assert_eq!(vm.last_score("#main.a", "MCPP.var"), Some(5));
+        assert_eq!(vm.last_score("#main.b", "MCPP.var"), Some(-99));
+        assert_eq!(vm.last_score("#main.c", "MCPP.var"), Some(4));
+        // The variables of a function and the temporaries of the blocks around `return`
+        // are freed whichever `return` it leaves by
+        for variable in [
+            "#root.n", "#root.i", "#sign.x", "#third.FOR_0.k",
+            "#root.WHILE_CONDITION_0", "#root.while_0.IF_CONDITION_0", "#sign.IF_CONDITION_0",
+            "#sign.else_0.IF_CONDITION_0", "#third.FOR_END_0", "#third.for_0.IF_CONDITION_0"
+        ] {
+            assert_eq!(vm.score(variable, "MCPP.var"), None, "`{}` is left on {}", variable, target);
+        }
+    }
+}
+#[test]
+fn target_test() {
+    let compile = |target:&str, source:&str| {
+        let mut compiler = Compiler::from("MCPP");
+        compiler.target = target.parse().unwrap();
+        crate::compile_with(compiler, source)
+    };
+    let joining = r#"let name:str = "Steve"; let greet = "Hi, " + name;"#;
+    assert!(compile("1.20.2", joining).is_ok());
+    let error = compile("1.20.1", joining).unwrap_err();
+    assert!(matches!(error, Spanned { inner: CompileErrorKind::UnsupportedByTarget(_, _, _), span: s } if s.line == 1 && s.column == 25));
+    assert!(matches!(
+        compile("1.20.1", r#"let a = "a" + "b";"#),
+        Err(Spanned { inner: CompileErrorKind::UnsupportedByTarget(_, Feature::FunctionMacros, _), .. })
+    ));
+    assert!(matches!(
+        compile("1.19.4", r#"let a = 1; native!("say {a}");"#),
+        Err(Spanned { inner: CompileErrorKind::UnsupportedByTarget(_, _, _), .. })
+    ));
+    assert!(compile("1.15", r#"let a = 1; native!("say a");"#).is_ok());
+}
+
 trait Serialisable {
     fn serialise(&self, compiler:&mut Compiler) -> Result<Vec<CommandAST>, CompileError>;
 }
 
-impl MCFunctionizable for AST {
+impl MCFunctionizable for Ast {
     fn mcfunctionate(&self, compiler:&mut Compiler) -> Result<MCFunction, CompileError> {
         match self {
-            AST::CodeBlock(c) => c.mcfunctionate(compiler),
-            AST::IfSyntax(i) => i.mcfunctionate(compiler),
-            AST::WhileSyntax(w) => w.mcfunctionate(compiler),
-            _ => CodeBlock { inside: vec![self.clone()] }.mcfunctionate(compiler)
+            Ast::CodeBlock(c) => c.mcfunctionate(compiler),
+            Ast::IfSyntax(i) => i.mcfunctionate(compiler),
+            Ast::WhileSyntax(w) => w.mcfunctionate(compiler),
+            Ast::ForSyntax(f) => f.mcfunctionate(compiler),
+            Ast::FunctionDefinement(f) => f.mcfunctionate(compiler),
+            _ => CodeBlock { inside: vec![self.clone()], span: Span::default() }.mcfunctionate(compiler)
         }
     }
 }
@@ -255,77 +1150,200 @@ impl Serialisable for VariableDefinement {
                 Some(s) => s,
                 None => match &self.initialise {
                     Some(s) => s.rhs.to_calc_reserv(compiler)?.guess_type()?,
-                    None => Err(CompileError::TheTypeOfAIndentifierWontBeConfirmed(self.identifier.clone()))?
+                    None => Err(CompileErrorKind::TheTypeOfAIndentifierWontBeConfirmed(self.identifier.clone()).at(self.span))?
                 }
             }
         };
         let cast = match &self.initialise {
             Some(s) => s.rhs
                 .to_calc_reserv(compiler)?
-                .serialise(&score)?,
+                .serialise(compiler, &score)?,
             None => Vec::new()
         };
+        compiler.declarations.insert(score.get_mcname(), self.span);
         compiler.variables.push(score);
         Ok(cast)
     }
 }
 struct CalcReserv {
-    tokens: Vec<IToken>
-}
-impl From<Vec<IToken>> for CalcReserv {
-    fn from(value: Vec<IToken>) -> Self {
-        CalcReserv { tokens: value }
-    }
+    tokens: Vec<Spanned<IToken>>,
+    span: Span
 }
 impl CalcReserv {
-    fn serialise(&self, store_to:&Scoreboard) -> Result<Vec<CommandAST>, CompileError> {
-        let mut commands: Vec<CommandAST> = Vec::new();
-        let mut stack: Vec<IToken> = Vec::new();
+    fn serialise(&self, compiler:&mut Compiler, store_to:&Scoreboard) -> Result<Vec<CommandAST>, CompileError> {
+        // Each operand keeps the commands evaluating it, so that they can be skipped by a short circuit
+        let mut stack: Vec<(IToken, Vec<CommandAST>)> = Vec::new();
         let mut temp_scores:Vec<Scoreboard> = Vec::new();
 
-        for token in &self.tokens {
+        for Spanned { inner: token, span } in &self.tokens {
             // Move values to the stack
             if token.is_value() {
                 match token {
-                    IToken::Scr(_) | IToken::Fnc(_, _) | IToken::Mcr(_, _) | IToken::Int(_) | IToken::Flt(_) | IToken::Bln(_) => {
-                        stack.push(token.clone());
+                    // The returned value is copied at once so that the following calls can't overwrite it
+                    IToken::Fnc(f, a) => {
+                        let tail = std::mem::take(&mut compiler.tail_call);
+                        if let Some(caller) = compiler.returning.as_ref().map(|r| r.scope[0].clone()) {
+                            compiler.calls.push((caller, f.name.clone(), tail, *span));
+                        }
+                        let mut evaluated = f.call_with(compiler, a).map_err(|e| e.at(*span))?;
+                        let returned = f.return_slot();
+                        if f.returning_type == Type::None {
+                            stack.push((IToken::Scr(returned), evaluated));
+                            continue;
+                        }
+                        let result_container = scoreboard::get_calc_result_temp(f.returning_type, compiler.next_temp_id());
+                        evaluated.extend(result_container.assign(&IToken::Scr(returned.clone()))?);
+                        evaluated.extend(returned.free());
+                        temp_scores.push(result_container.clone());
+                        stack.push((IToken::Scr(result_container), evaluated));
+                    },
+                    // A macro stores its value into a new container, unless it has no value
+                    IToken::Mcr(m, a, datatype) => {
+                        let definition = compiler
+                            .get_macro(m)
+                            .ok_or(CompileErrorKind::UndefinedIdentifierReferenced(format!("{}!", m)).at(*span))?;
+                        let result_container = scoreboard::get_calc_result_temp(*datatype, compiler.next_temp_id());
+                        let evaluated = definition.expand(a, compiler, &result_container).map_err(|e| e.at(*span))?;
+                        if *datatype != Type::None {
+                            temp_scores.push(result_container.clone());
+                        }
+                        stack.push((IToken::Scr(result_container), evaluated));
                     },
-                    _ => return Err(CompileError::UnsupportedLiteralType(token.clone())),
+                    IToken::Str(s) => {
+                        compiler.check_joinable(s, *span)?;
+                        stack.push((token.clone(), Vec::new()));
+                    },
+                    IToken::Scr(_) | IToken::Int(_) | IToken::Flt(_) | IToken::Bln(_) => {
+                        stack.push((token.clone(), Vec::new()));
+                    },
+                    _ => return Err(CompileErrorKind::UnsupportedLiteralType(Box::new(token.clone())).at(*span)),
                 }
+            // A unary operator calculates on a copy of its operand
+            } else if let IToken::Oper(Oper::Unary(unary)) = token {
+                let (operand, mut evaluated) = stack.pop().ok_or(CompileErrorKind::InvalidFormulaStructure("Not enough operands for operator".to_string()).at(*span))?;
+                let datatype = operand.get_datatype().unwrap();
+                let result_type = unary
+                    .get_type(&datatype)
+                    .ok_or(CompileErrorKind::UndefinedUnaryOperation(Oper::Unary(unary.clone()), datatype).at(*span))?;
+                if let Some(folded) = unary.fold(&operand) {
+                    stack.push((folded, evaluated));
+                    continue;
+                }
+                // An intermediate result is calculated in place, but a variable mustn't be changed
+                let result_container = match &operand {
+                    IToken::Scr(s) if temp_scores.iter().any(|t| t.get_mcname() == s.get_mcname()) => s.clone(),
+                    _ => {
+                        // A literal is scaled on its assignment not to lose its digits, e.g. `0.12345 as float<5>`
+                        let container_type = match (&operand, unary) {
+                            (IToken::Int(_) | IToken::Flt(_), Unary::Cast(to)) if to.is_number() => *to,
+                            _ => datatype
+                        };
+                        let container = scoreboard::get_calc_result_temp(container_type, compiler.next_temp_id());
+                        evaluated.extend(container.assign(&operand).map_err(|e| e.at(*span))?);
+                        temp_scores.push(container.clone());
+                        container
+                    }
+                };
+                evaluated.extend(unary.calc(&result_container).map_err(|e| e.at(*span))?);
+                let mut result = result_container;
+                result.datatype = result_type;
+                stack.push((IToken::Scr(result), evaluated));
             // Calcate if a operator poped
             } else if let IToken::Oper(operator) = token {
                 // Get lhs and rhs
-                let rhs_board = stack.pop().ok_or(CompileError::InvalidFormulaStructure("Not enough operands for operator".to_string()))?;
-                let lhs_board = stack.pop().ok_or(CompileError::InvalidFormulaStructure("Not enough operands for operator".to_string()))?;
-                // A calc result expect a container scoreboard
-                let result_container = Scoreboard {
-                    name: format!("CALC_RESULT_{}", scoreboard::generate_random_id(scoreboard::TEMP_ID_LEN)),
-                    scope: vec!["TEMP".to_string()],
-                    datatype: lhs_board.get_datatype().unwrap()
-                };
-                // let TEMP.CALC_RESULT_XXX = LHS;
-                // TEMP.CALC_RESULT_XXX [OPERATOR]= RHS;
-                commands.extend(result_container.assign(&lhs_board)?);
-                commands.extend(
-                    match operator {
-                        Oper::Arithmetic(a) => a.calc(&result_container, &rhs_board)?,
-                        Oper::Comparison(c) => c.calc(&result_container, &rhs_board)?,
-                        Oper::Logical(l) => l.calc(&result_container, &rhs_board)?
+                let (rhs_board, rhs_commands) = stack.pop().ok_or(CompileErrorKind::InvalidFormulaStructure("Not enough operands for operator".to_string()).at(*span))?;
+                let (lhs_board, mut evaluated) = stack.pop().ok_or(CompileErrorKind::InvalidFormulaStructure("Not enough operands for operator".to_string()).at(*span))?;
+                // e.g. A comparison between ints results a bool
+                let datatype = operator
+                    .get_type(&lhs_board.get_datatype().unwrap(), &rhs_board.get_datatype().unwrap())
+                    .ok_or(CompileErrorKind::UndefinedOperation(
+                        lhs_board.get_datatype().unwrap(), operator.clone(), rhs_board.get_datatype().unwrap()
+                    ).at(*span))?;
+                // An arithmetic results in the type of its left side, e.g. `1 + 2.5` is truncated into an int
+                if let (true, Oper::Arithmetic(_)) = (compiler.strict, operator) {
+                    let right = rhs_board.get_datatype().unwrap();
+                    let narrowed = match rhs_board {
+                        IToken::Flt(_) => datatype == Type::Int,
+                        _ => right.narrows_to(&datatype)
+                    };
+                    if narrowed {
+                        return Err(CompileErrorKind::ImplicitNarrowing(right, datatype).at(*span));
                     }
+                }
+                // A calc result expect a container scoreboard
+                let result_container = scoreboard::get_calc_result_temp(
+                    lhs_board.get_datatype().unwrap(),
+                    compiler.next_temp_id()
                 );
                 // Add the scoreboard to temp boards to free the score after it become unnecessary
                 temp_scores.push(result_container.clone());
-                stack.push(IToken::Scr(result_container));
+                // A comparison of strings results a score besides the copied string
+                if result_container.datatype == Type::Str && datatype != Type::Str {
+                    let mut result = result_container.clone();
+                    result.datatype = datatype;
+                    temp_scores.push(result);
+                }
+                // Joining strings needs function macros, which the target is checked for
+                if datatype == Type::Str {
+                    compiler.require_concat()?;
+                }
+                match operator {
+                    // The right side is evaluated in a function, which is called only if the left side doesn't decide the result
+                    Oper::Logical(l) if !rhs_commands.is_empty() => {
+                        evaluated.extend(result_container.assign(&lhs_board).map_err(|e| e.at(*span))?);
+                        let mut right = rhs_commands;
+                        right.extend(result_container.assign(&rhs_board).map_err(|e| e.at(*span))?);
+                        let name = format!("{}_{}", l.get_name(), compiler.next_block_id());
+                        let mut mcfunction = MCFunction::of_commands(name, &right, compiler, *span);
+                        mcfunction.callment_prefix = ExecuteConstructer
+                            ::new()
+                            .matches(&result_container, false, (l.evaluates_right_on() as i32).to_string())
+                            .build();
+                        evaluated.push(CommandAST::Native(mcfunction.call(compiler)));
+                        compiler.compiled.push(mcfunction);
+                    },
+                    // let TEMP.CALC_RESULT_XXX = LHS;
+                    // TEMP.CALC_RESULT_XXX [OPERATOR]= RHS;
+                    _ => {
+                        evaluated.extend(rhs_commands);
+                        evaluated.extend(result_container.assign(&lhs_board).map_err(|e| e.at(*span))?);
+                        evaluated.extend(
+                            match operator {
+                                Oper::Arithmetic(a) => a.calc(&result_container, &rhs_board),
+                                Oper::Comparison(c) => c.calc(&result_container, &rhs_board),
+                                Oper::Logical(l) => l.calc(&result_container, &rhs_board),
+                                Oper::Unary(_) => unreachable!()
+                            }.map_err(|e| e.at(*span))?
+                        );
+                    }
+                }
+                let mut result = result_container;
+                result.datatype = datatype;
+                stack.push((IToken::Scr(result), evaluated));
             } else {
                 // There's no token that isn't value nor operator in formula token, right?
                 unreachable!("Non-operator, non-value token found in RPN: {:?}", token);
             }
         }
-        if stack.len() == 1 {
-            commands.extend(store_to.assign(&&stack.pop().unwrap())?);
+        let mut commands = if stack.len() == 1 {
+            let (result, mut commands) = stack.pop().unwrap();
+            let given = result.get_datatype().unwrap();
+            // A float literal has no decimal places of its own, so only truncating it into an int drops its digits
+            let narrowed = match result {
+                IToken::Flt(_) => store_to.datatype == Type::Int,
+                _ => given.narrows_to(&store_to.datatype)
+            };
+            if compiler.strict && narrowed {
+                return Err(CompileErrorKind::ImplicitNarrowing(given, store_to.datatype).at(self.span));
+            }
+            // A formula without any value is evaluated only for its side effects
+            if store_to.datatype != Type::None || given != Type::None {
+                commands.extend(store_to.assign(&result).map_err(|e| e.at(self.span))?);
+            }
+            commands
         } else {
-            return Err(CompileError::UnbalancedParentheses);
-        }
+            return Err(CompileErrorKind::UnbalancedParentheses.at(self.span));
+        };
         // Free temp scores generated for calcation
         for tmp in temp_scores {
             commands.extend(tmp.free());
@@ -334,36 +1352,43 @@ impl CalcReserv {
     }
     fn guess_type(&self) -> Result<Type, CompileError> {
         let mut stack: Vec<Type> = Vec::new();
-        for token in &self.tokens {
+        for Spanned { inner: token, span } in &self.tokens {
             // Move values to the stack
             if token.is_value() {
                 match token {
-                    IToken::Scr(_) | IToken::Fnc(_, _) | IToken::Mcr(_, _) | IToken::Int(_) | IToken::Flt(_) | IToken::Bln(_) => {
+                    IToken::Scr(_) | IToken::Fnc(_, _) | IToken::Mcr(_, _, _) | IToken::Int(_) | IToken::Flt(_) | IToken::Bln(_) | IToken::Str(_) => {
                         stack.push(token.get_datatype().unwrap());
                     },
-                    _ => return Err(CompileError::UnsupportedLiteralType(token.clone())),
+                    _ => return Err(CompileErrorKind::UnsupportedLiteralType(Box::new(token.clone())).at(*span)),
                 }
+            } else if let IToken::Oper(Oper::Unary(unary)) = &token {
+                let operand = stack.pop().ok_or(CompileErrorKind::InvalidFormulaStructure("Not enough operands for operator".to_string()).at(*span))?;
+                stack.push(unary
+                    .get_type(&operand)
+                    .ok_or(CompileErrorKind::UndefinedUnaryOperation(Oper::Unary(unary.clone()), operand).at(*span))?
+                );
             // Calcate if a operator poped
             } else if let IToken::Oper(operator) = &token {
                 // Get lhs and rhs
-                let rhs = stack.pop().ok_or(CompileError::InvalidFormulaStructure("Not enough operands for operator".to_string()))?;
-                let lhs = stack.pop().ok_or(CompileError::InvalidFormulaStructure("Not enough operands for operator".to_string()))?;
+                let rhs = stack.pop().ok_or(CompileErrorKind::InvalidFormulaStructure("Not enough operands for operator".to_string()).at(*span))?;
+                let lhs = stack.pop().ok_or(CompileErrorKind::InvalidFormulaStructure("Not enough operands for operator".to_string()).at(*span))?;
                 stack.push(match operator {
                     Oper::Arithmetic(a) => a
                         .get_type(&lhs, &rhs)
-                        .ok_or(CompileError::UndefinedOperation(
-                            lhs.clone(), operator.clone(), rhs.clone()
-                        ))?,
+                        .ok_or(CompileErrorKind::UndefinedOperation(
+                            lhs, operator.clone(), rhs
+                        ).at(*span))?,
                     Oper::Comparison(b) => b
                         .get_type(&lhs, &rhs)
-                        .ok_or(CompileError::UndefinedOperation(
-                            lhs.clone(), operator.clone(), rhs.clone()
-                        ))?,
+                        .ok_or(CompileErrorKind::UndefinedOperation(
+                            lhs, operator.clone(), rhs
+                        ).at(*span))?,
                     Oper::Logical(l) => l
                         .get_type(&lhs, &rhs)
-                        .ok_or(CompileError::UndefinedOperation(
-                            lhs.clone(), operator.clone(), rhs.clone()
-                        ))?,
+                        .ok_or(CompileErrorKind::UndefinedOperation(
+                            lhs, operator.clone(), rhs
+                        ).at(*span))?,
+                    Oper::Unary(_) => unreachable!()
                 })
             } else {
                 // There's no token that isn't value nor operator in formula token, right?
@@ -373,16 +1398,16 @@ impl CalcReserv {
         if stack.len() == 1 {
             Ok(stack.pop().unwrap())
         } else {
-            return Err(CompileError::UnbalancedParentheses);
+            Err(CompileErrorKind::UnbalancedParentheses.at(self.span))
         }
     }
-    fn to_be(&self, store_to:&Scoreboard) -> Result<Vec<CommandAST>, CompileError> {
+    fn to_be(&self, compiler:&mut Compiler, store_to:&Scoreboard) -> Result<Vec<CommandAST>, CompileError> {
         let evaluated_stored = Scoreboard {
-            name: format!("TO_BE_{}", scoreboard::generate_random_id(32)),
+            name: format!("TO_BE_{}", compiler.next_temp_id()),
             scope: vec!["TEMP".to_string()],
             datatype: self.guess_type()?
         };
-        let mut result = self.serialise(&evaluated_stored)?;
+        let mut result = self.serialise(compiler, &evaluated_stored)?;
         result.extend(FormulaConstructer
             ::new()
             .boolify_num_comparison(
@@ -401,9 +1426,29 @@ impl RPNFormula {
     fn to_calc_reserv(&self, compiler:&Compiler) -> Result<CalcReserv, CompileError> {
         let mut i_tokenized = Vec::new();
         for t in &self.formula_tokens {
-            i_tokenized.push(t.clone().i_tokenize(compiler)?);
+            i_tokenized.push(Spanned::new(
+                t.inner.clone().i_tokenize(compiler).map_err(|e| e.at(t.span))?,
+                t.span
+            ));
         }
-        Ok(CalcReserv::from(i_tokenized))
+        Ok(CalcReserv { tokens: i_tokenized, span: self.span })
+    }
+}
+
+impl Tuple {
+    fn rpn_at(&self, index:usize) -> Result<RPNFormula, CompileError> {
+        let formula = self.inside
+            .get(index)
+            .ok_or(CompileError::from(SyntaxError::ArgumentCountMismatch(self.span)))?;
+        formula.clone().into_rpn().map_err(CompileError::from)
+    }
+    /// The type of the argument's value
+    pub fn type_of(&self, index:usize, compiler:&Compiler) -> Result<Type, CompileError> {
+        self.rpn_at(index)?.to_calc_reserv(compiler)?.guess_type()
+    }
+    /// Evaluates the argument, storing its value into the score
+    pub fn evaluate(&self, index:usize, compiler:&mut Compiler, store_to:&Scoreboard) -> Result<Vec<CommandAST>, CompileError> {
+        self.rpn_at(index)?.to_calc_reserv(compiler)?.serialise(compiler, store_to)
     }
 }
 
@@ -411,19 +1456,157 @@ impl Serialisable for Assignment {
     fn serialise(&self, compiler:&mut Compiler) -> Result<Vec<CommandAST>, CompileError> {
         let store_to = compiler
             .get_score(&self.lhs)
-            .ok_or(CompileError::UndefinedIdentifierReferenced(self.lhs.clone()))?;
-        self.rhs.to_calc_reserv(compiler)?.serialise(store_to)
+            .ok_or(CompileErrorKind::UndefinedIdentifierReferenced(self.lhs.clone()).at(self.span))?
+            .clone();
+        let rhs = self.rhs.to_calc_reserv(compiler)?;
+        rhs.serialise(compiler, &store_to).map_err(|e| match e.inner {
+            // Points where the variable got its type
+            CompileErrorKind::InvalidRHS(_) => match rhs.guess_type() {
+                Ok(given) => CompileErrorKind::MismatchedAssignment(
+                    self.lhs.clone(),
+                    store_to.datatype,
+                    given,
+                    compiler.declarations.get(&store_to.get_mcname()).copied().unwrap_or_default()
+                ).at(self.span),
+                Err(e) => e
+            },
+            _ => e
+        })
     }
 }
 impl Serialisable for Formula {
     fn serialise(&self, compiler:&mut Compiler) -> Result<Vec<CommandAST>, CompileError> {
-        let rpn = match self.clone().to_rpn() {
+        let rpn = match self.clone().into_rpn() {
             Ok(o) => o,
-            Err(e) => Err(CompileError::ASyntaxErrorOccured(e.clone()))?
+            Err(e) => Err(CompileError::from(e.clone()))?
         }.to_calc_reserv(compiler)?;
         let store_to = scoreboard::get_calc_result_temp(
-            rpn.guess_type()?
+            rpn.guess_type()?,
+            compiler.next_temp_id()
         );
-        rpn.serialise(&store_to)
+        let mut commands = rpn.serialise(compiler, &store_to)?;
+        // Nothing is stored if it has no value, e.g. a call of a function returning nothing
+        if store_to.datatype != Type::None {
+            commands.extend(store_to.free());
+        }
+        Ok(commands)
+    }
+}
+/// The flag of a function which has returned, used unless the `return` command is
+fn get_returned_flag(return_slot:&Scoreboard) -> Scoreboard {
+    Scoreboard { name: "RETURNED".to_string(), scope: return_slot.scope.clone(), datatype: Type::Int }
+}
+impl Serialisable for ReturnStatement {
+    fn serialise(&self, compiler:&mut Compiler) -> Result<Vec<CommandAST>, CompileError> {
+        let return_slot = compiler
+            .returning
+            .clone()
+            .ok_or(CompileErrorKind::ReturnOutsideOfFunction.at(self.span))?;
+        let mut serialised = match &self.value {
+            Some(v) => {
+                let reserv = v.to_calc_reserv(compiler)?;
+                // Nothing of the caller is used after the call which is the value itself
+                compiler.tail_call = matches!(reserv.tokens[..], [Spanned { inner: IToken::Fnc(_, _), .. }]);
+                let serialised = reserv.serialise(compiler, &return_slot);
+                compiler.tail_call = false;
+                serialised?
+            },
+            None if return_slot.datatype != Type::None => {
+                return Err(CompileErrorKind::MissingReturnValue(return_slot.datatype).at(self.span));
+            },
+            None => Vec::new()
+        };
+        compiler.return_count += 1;
+        if compiler.target.supports(Feature::ReturnCommand) {
+            // Nothing after `return 1` runs, so the variables of the function
+            // and the temporaries of the blocks around are freed before it
+            serialised.extend(compiler
+                .variables
+                .iter()
+                .filter(|v| v.scope.starts_with(&return_slot.scope))
+                .chain(compiler.held_temps.iter())
+                .flat_map(|v| v.free())
+                .collect::<Vec<CommandAST>>());
+            serialised.push(CommandAST::Native("return 1".to_string()));
+        } else {
+            // The following statements check the flag as they do after `break`
+            let returned = get_returned_flag(&return_slot);
+            serialised.extend(FormulaConstructer::new().assign_num(&returned, 1).build());
+            compiler.jumps.push(returned);
+        }
+        Ok(serialised)
+    }
+}
+impl FunctionDefinement {
+    /// The function without its body, whose arguments are scoped by its name
+    fn signature(&self) -> MCFunction {
+        // Functions live at the root whereever they are defined
+        let func_scope = vec![self.func_name.clone()];
+        MCFunction {
+            name: self.func_name.clone(),
+            inside: String::new(),
+            path: Vec::new(),
+            callment_prefix: String::new(),
+            preprocess: String::new(),
+            postprocess: String::new(),
+            returning_type: self.datatype,
+            arguments: self.arguments
+                .iter()
+                .map(|a| Scoreboard {
+                    name: a.name.clone(),
+                    scope: func_scope.clone(),
+                    datatype: a.datatype
+                })
+                .collect(),
+            defined_at: self.span,
+            returns: false
+        }
+    }
+}
+impl MCFunctionizable for FunctionDefinement {
+    fn mcfunctionate(&self, compiler:&mut Compiler) -> Result<MCFunction, CompileError> {
+        let signature = self.signature();
+        let func_scope = vec![self.func_name.clone()];
+        for (score, argument) in signature.arguments.iter().zip(self.arguments.iter()) {
+            compiler.declarations.insert(score.get_mcname(), argument.span);
+        }
+        compiler.variables.extend(signature.arguments.clone());
+        let outer_scope = std::mem::take(&mut compiler.scope);
+        // The loops around the definition can't be left from inside the function
+        let outer_loops = std::mem::take(&mut compiler.loops);
+        let outer_held_temps = std::mem::take(&mut compiler.held_temps);
+        let outer_return_count = compiler.return_count;
+        let return_slot = scoreboard::get_return_slot(func_scope, self.datatype);
+        let outer_returning = compiler.returning.replace(return_slot.clone());
+        let mark = compiler.jumps.len();
+        let compiled = self.block.compile_named(self.func_name.clone(), compiler);
+        let jumps = compiler.jumps_since(mark);
+        compiler.jumps.truncate(mark);
+        compiler.scope = outer_scope;
+        compiler.loops = outer_loops;
+        compiler.held_temps = outer_held_temps;
+        compiler.return_count = outer_return_count;
+        compiler.returning = outer_returning;
+
+        let mut mcfunction = compiled?;
+        if self.datatype != Type::None && !self.block.always_returns() {
+            return Err(CompileErrorKind::MissingReturn(self.func_name.clone(), self.datatype).at(self.span));
+        }
+        // The flag is cleared first, since the statements after `return` run only while it's 0
+        let returned = get_returned_flag(&return_slot);
+        if jumps.iter().any(|j| j.get_mcname() == returned.get_mcname()) {
+            let clear = FormulaConstructer::new().assign_num(&returned, 0).build();
+            mcfunction.inside = join_lines(vec![
+                compiler.c_ast_to_string(&clear),
+                mcfunction.inside.clone(),
+                compiler.c_ast_to_string(&returned.free())
+            ]);
+        }
+        // Returning from the function doesn't make its callers return
+        mcfunction.returns = false;
+        mcfunction.returning_type = signature.returning_type;
+        mcfunction.arguments = signature.arguments;
+        mcfunction.defined_at = signature.defined_at;
+        Ok(mcfunction)
     }
 }
\ No newline at end of file
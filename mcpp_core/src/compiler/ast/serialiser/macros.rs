@@ -1,14 +1,122 @@
-use crate::{compiler::ast::{FToken, SyntaxError, Tuple}, evaluater::scoreboard::command_ast::CommandAST};
+use super::MCFunction;
+use crate::compiler::{ast::{SyntaxError, Tuple}, CompileError, CompileErrorKind, Compiler};
+use crate::compiler::target::Feature;
+use crate::evaluater::scoreboard::command_ast::{CommandAST, FormulaConstructer};
+use crate::evaluater::scoreboard::get_macro_arguments;
+use crate::evaluater::{Scoreboard, Type};
 
-pub fn solve_native(arg:Tuple) -> Result<CommandAST, SyntaxError> {
-    if arg.inside.len() == 0 {
-        let inside = arg.inside.get(0).unwrap();
-        if let Some(FToken::Str(s)) = inside.formula_tokens.get(0) {
-            Ok(CommandAST::Native(s.clone()))
-        } else {
-            Err(SyntaxError::ArgumentCountMismatch)
+/// A macro called as `name!(...)`, which is registered to the compiler by `Compiler::register_macro`
+pub trait Macro {
+    /// The type of the value, which is decided before the macro is expanded
+    fn returning_type(&self, arguments:&Tuple, compiler:&Compiler) -> Result<Type, CompileError>;
+    /// The commands running the macro, which store its value into the container unless the type is none
+    fn expand(&self, arguments:&Tuple, compiler:&mut Compiler, container:&Scoreboard) -> Result<Vec<CommandAST>, CompileError>;
+}
+
+/// `native!(command)` or `native!(type, command)`, which runs a command of Minecraft
+pub struct Native;
+impl Native {
+    fn solve(arguments:&Tuple) -> Result<(Type, String), CompileError> {
+        let expected = |what:&str, index:usize| CompileError::from(
+            SyntaxError::ExpectedAToken(what.to_string(), arguments.span_at(index))
+        );
+        let datatype = match arguments.len() {
+            1 => Type::None,
+            2 => arguments.type_at(0).ok_or(expected("data type", 0))?,
+            _ => return Err(CompileError::from(SyntaxError::ArgumentCountMismatch(arguments.span)))
+        };
+        let last = arguments.len() - 1;
+        let command = arguments.string_at(last).ok_or(expected("command string", last))?;
+        Ok((datatype, command.to_string()))
+    }
+}
+impl Macro for Native {
+    fn returning_type(&self, arguments:&Tuple, _:&Compiler) -> Result<Type, CompileError> {
+        Ok(Self::solve(arguments)?.0)
+    }
+    fn expand(&self, arguments:&Tuple, compiler:&mut Compiler, container:&Scoreboard) -> Result<Vec<CommandAST>, CompileError> {
+        let (datatype, command) = Self::solve(arguments)?;
+        let (command, names) = template(&command);
+        let captured = capture(datatype, command, container);
+        if names.is_empty() {
+            return Ok(captured);
+        }
+        // The command is run in a function macro, whose arguments are the interpolated variables copied into the storage
+        compiler.target.require(Feature::FunctionMacros, "interpolating variables into `native!`", arguments.span)?;
+        let macro_arguments = get_macro_arguments("NATIVE");
+        let mut f_construct = FormulaConstructer::new();
+        for name in &names {
+            let span = arguments.span_at(arguments.len() - 1);
+            let variable = compiler
+                .get_score(name)
+                .ok_or(CompileErrorKind::UndefinedIdentifierReferenced(name.clone()).at(span))?
+                .clone();
+            let argument = macro_arguments.child(name, variable.datatype);
+            match variable.datatype {
+                Type::Str => f_construct.copy_string(&argument, &variable),
+                _ => f_construct.store_score(&argument, &variable)
+            };
+        }
+        let name = format!("native_{}", compiler.next_block_id());
+        let mut function = MCFunction::of_commands(name, &captured, compiler, arguments.span);
+        function.inside = function.inside
+            .lines()
+            .map(|l| if l.contains("$(") {format!("${}", l)} else {l.to_string()})
+            .collect::<Vec<String>>()
+            .join("\n");
+        f_construct
+            .call_with(function.get_full_path(), &macro_arguments)
+            .remove_string(&macro_arguments);
+        compiler.compiled.push(function);
+        Ok(f_construct.build())
+    }
+}
+
+/// Runs the command, storing its result into the container unless the type is none
+fn capture(datatype:Type, command:String, container:&Scoreboard) -> Vec<CommandAST> {
+    if datatype == Type::None {
+        return vec![CommandAST::Native(command)];
+    }
+    let mut f_construct = FormulaConstructer::new();
+    f_construct.store_result(container, command);
+    match datatype {
+        Type::Float(p) => {f_construct.fltify(container, p);},
+        // True has to be 1 so that it can be calculated with the others
+        Type::Bool => {f_construct.validate_bool(container);},
+        _ => ()
+    }
+    f_construct.build()
+}
+
+/// Replaces `{name}` in the command with `$(name)` of a function macro, and returns the names.
+/// `{{` and `}}` are braces themselves, and the other braces are left as they are, e.g. of SNBT.
+fn template(command:&str) -> (String, Vec<String>) {
+    let mut replaced = String::new();
+    let mut names:Vec<String> = Vec::new();
+    let mut rest = command;
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            replaced.push(c);
+            rest = &rest[2..];
+            continue;
+        }
+        let placeholder = rest
+            .strip_prefix('{')
+            .and_then(|r| r.split_once('}'))
+            .filter(|(name, _)| {
+                name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                    && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            });
+        if let Some((name, after)) = placeholder {
+            replaced.push_str(&format!("$({})", name));
+            if !names.iter().any(|n| n == name) {
+                names.push(name.to_string());
+            }
+            rest = after;
+            continue;
         }
-    } else {
-        Err(SyntaxError::ArgumentCountMismatch)
+        replaced.push(c);
+        rest = &rest[c.len_utf8()..];
     }
+    (replaced, names)
 }
\ No newline at end of file
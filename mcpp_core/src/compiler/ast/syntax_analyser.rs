@@ -1,230 +1,615 @@
 use super::*;
 
 use crate::compiler::Oper;
-use crate::compiler::Token;
+use crate::compiler::{SpannedToken, Token};
 use crate::compiler::ast;
 
 use crate::evaluater::scoreboard::arithmetic_operation::Arithmetic;
 use crate::evaluater::scoreboard::logical_operation::Logical;
 use crate::evaluater::scoreboard::comparison_operation::Comparison;
+use crate::evaluater::scoreboard::unary_operation::Unary;
+use crate::evaluater::scoreboard::MAX_FLOAT_PLACES;
 
 #[test]
 fn test() {
-    let token = vec![
+    let _token = [
         Token::LBrace, Token::If, Token::Ident("a".to_string()), Token::Gt, Token::Int(10), Token::LBrace,
         Token::Let
     ];
 }
+#[test]
+fn fn_parse_test() {
+    let token = crate::tokeniser::tokenize(
+        "{ fn f(a:int, b:float) -> int { return a; } let x = f(1, (2.5)) * 3; }".to_string()
+    ).unwrap();
+    let block = SyntaxAnalyser::from(token).get_block().unwrap();
+    match &block.inside[..] {
+        [Ast::FunctionDefinement(f), Ast::LetStatement(_)] => {
+            assert_eq!(f.func_name, "f");
+            assert_eq!(f.datatype, Type::Int);
+            assert_eq!(f.arguments.len(), 2);
+            assert!(matches!(f.block.inside[..], [Ast::Return(_)]));
+        },
+        _ => panic!("{:?}", block)
+    }
+}
+#[test]
+fn doc_comment_test() {
+    let token = crate::tokeniser::tokenize(
+        "{ /// Adds them\n fn f(/// left\n a:int, b:int) -> int { return a + b; }\n let x = f(\n /// one\n 1, 2) +\n /// three\n 3; }".to_string()
+    ).unwrap();
+    let block = SyntaxAnalyser::from(token).get_block().unwrap();
+    match &block.inside[..] {
+        [Ast::FunctionDefinement(f), Ast::LetStatement(_)] => assert_eq!(f.arguments.len(), 2),
+        _ => panic!("{:?}", block)
+    }
+}
+#[test]
+fn span_test() {
+    let source = "{\n  let a = 1;\n  let b = a +\n    c * 2;\n}";
+    let token = crate::tokeniser::tokenize(source.to_string()).unwrap();
+    let block = SyntaxAnalyser::from(token).get_block().unwrap();
+    match &block.inside[..] {
+        [Ast::LetStatement(_), Ast::LetStatement(b)] => {
+            assert_eq!((b.span.line, b.span.column), (3, 3));
+            let rhs = &b.initialise.as_ref().unwrap().rhs;
+            assert_eq!(&source[rhs.span.start..rhs.span.end], "a +\n    c * 2");
+            let c = rhs.formula_tokens.iter().find(|t| matches!(t.inner, FToken::Scr(ref s) if s == "c")).unwrap();
+            assert_eq!((c.span.line, c.span.column), (4, 5));
+        },
+        _ => panic!("{:?}", block)
+    }
+    let token = crate::tokeniser::tokenize("{\n  let a = 1\n}".to_string()).unwrap();
+    let error = SyntaxAnalyser::from(token).get_block().unwrap_err();
+    assert_eq!(error.to_string(), "3:1: expected `;`");
+}
+
+/// Renders the formula in reverse polish notation, marking the unary operators with `u`
+#[cfg(test)]
+fn rpn_of(formula:&str) -> String {
+    let token = crate::tokeniser::tokenize(format!("{{ let x = {}; }}", formula)).unwrap();
+    let block = SyntaxAnalyser::from(token).get_block().unwrap();
+    let Ast::LetStatement(l) = &block.inside[0] else {
+        panic!("{:?}", block)
+    };
+    l.initialise.as_ref().unwrap().rhs.formula_tokens
+        .iter()
+        .map(|t| match &t.inner {
+            FToken::Oper(Oper::Unary(u)) => format!("u{}", u.to_str()),
+            t => t.to_string()
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[test]
+fn precedence_test() {
+    // From the tightest, every binary operator is left associative
+    let levels = [
+        vec!["*", "/", "%"],
+        vec!["+", "-"],
+        vec!["<", "<=", ">", ">=", "==", "!="],
+        vec!["&"],
+        vec!["|"]
+    ];
+    let level_of = |o:&str| levels.iter().position(|l| l.contains(&o)).unwrap();
+    for l in levels.iter().flatten() {
+        for r in levels.iter().flatten() {
+            let expected = if level_of(l) <= level_of(r) {
+                format!("a b {} c {}", l, r)
+            } else {
+                format!("a b c {} {}", r, l)
+            };
+            assert_eq!(rpn_of(&format!("a {} b {} c", l, r)), expected, "`a {} b {} c`", l, r);
+        }
+        assert_eq!(rpn_of(&format!("-a {} !b", l)), format!("a u- b u! {}", l));
+    }
+    assert_eq!(rpn_of("- -a * (b - c) - d"), "a u- u- b c - * d -");
+    assert_eq!(rpn_of("!a & b | c & !(d == e)"), "a u! b & c d e == u! & |");
+    // A cast binds tighter than any binary operator, but follows the prefix ones
+    assert_eq!(rpn_of("-a as int * b - c as bool as int"), "a u- uas b * c uas uas -");
+}
 
 pub struct SyntaxAnalyser {
-    tokens: Vec<Token>,
+    tokens: Vec<SpannedToken>,
+    last_span: Span
 }
-fn expect_token_err(name:&str) -> SyntaxError {
-    SyntaxError::ExpectedAToken(name.to_string())
+
+/// The innermost `{` left open at the end of the tokens.
+/// A source wrapped in a block is checked before, or the brace would be closed by the one wrapping it.
+pub fn unclosed_brace(tokens:&[SpannedToken]) -> Option<Span> {
+    let mut opened = Vec::new();
+    for token in tokens {
+        match token.inner {
+            Token::LBrace => opened.push(token.span),
+            Token::RBrace => {
+                opened.pop();
+            },
+            _ => ()
+        }
+    }
+    opened.pop()
 }
 
-impl From<Vec<Token>> for SyntaxAnalyser {
-    fn from(value: Vec<Token>) -> Self {
-        SyntaxAnalyser { tokens: value }
+impl From<Vec<SpannedToken>> for SyntaxAnalyser {
+    fn from(value: Vec<SpannedToken>) -> Self {
+        // Doc comments are kept by the tokeniser, but nothing refers them yet,
+        // so they may stand anywhere between the tokens
+        let tokens = value.into_iter().filter(|t| !matches!(t.inner, Token::DocComment(_))).collect();
+        SyntaxAnalyser { tokens, last_span: Span::default() }
     }
 }
 impl SyntaxAnalyser {
     fn get_locally(&self, gap:usize) -> Option<&Token> {
-        self.tokens.get(gap)
+        self.tokens.get(gap).map(|t| &t.inner)
     }
     fn peek(&self) -> Option<&Token> {
-        self.tokens.get(0)
+        self.tokens.first().map(|t| &t.inner)
+    }
+    /// The span of the next token, or the end of the last one if there's no more token
+    fn peek_span(&self) -> Span {
+        match self.tokens.first() {
+            Some(t) => t.span,
+            None => Span { start: self.last_span.end, ..self.last_span }
+        }
+    }
+    /// The span from the given one to the last consumed token
+    fn span_from(&self, start:Span) -> Span {
+        start.to(&self.last_span)
     }
     fn consume(&mut self) -> Option<Token> {
         if self.tokens.is_empty() {
             None
         } else {
-            Some(self.tokens.remove(0))
+            let consumed = self.tokens.remove(0);
+            self.last_span = consumed.span;
+            Some(consumed.inner)
         }
     }
     fn expect(&mut self) -> Result<Token, SyntaxError> {
-        self.consume().ok_or(SyntaxError::TokenEndsUnexpectedly)
+        let span = self.peek_span();
+        self.consume().ok_or(SyntaxError::TokenEndsUnexpectedly(span))
+    }
+    /// The last consumed token isn't the expected one
+    fn expect_token_err(&self, name:&str) -> SyntaxError {
+        SyntaxError::ExpectedAToken(name.to_string(), self.last_span)
     }
     fn get_tuple(&mut self) -> Result<Tuple, SyntaxError> {
-        let syntax_error = SyntaxError::InvalidFormAs("tuple".to_string());
+        let start = self.peek_span();
         if !matches!(self.expect()?, Token::LParen) {
-            return Err(expect_token_err("("));
+            return Err(self.expect_token_err("`(`"));
         }
         let mut formulas = Vec::new();
+        if let Some(Token::RParen) = self.peek() {
+            self.consume();
+            return Ok(Tuple { inside : formulas, span : self.span_from(start) });
+        }
         loop {
-            formulas.push(Formula::from(self.get_formula()?));
+            formulas.push(self.get_formula()?);
             match self.expect()? {
                 Token::RParen => {break;},
                 Token::Comma => {continue;},
-                _ => {return Err(syntax_error);}
+                _ => {return Err(SyntaxError::InvalidFormAs("tuple".to_string(), self.last_span));}
             }
         }
-        Ok(Tuple { inside : formulas })
+        Ok(Tuple { inside : formulas, span : self.span_from(start) })
     }
-    fn get_formula(&mut self) -> Result<Vec<FToken>, SyntaxError> {
+    fn get_formula(&mut self) -> Result<Formula, SyntaxError> {
+        let formula_start = self.peek_span();
         let mut queue = Vec::new();
-        loop {
-            let found = match self.consume() {
-                Some(t) => match t {
-                    Token::Int(i) => FToken::Int(i),
-                    Token::Flt(f) => FToken::Flt(f),
-                    Token::Bln(b) => FToken::Bln(b),
-                    Token::Ident(i) => if let Some(t) = self.get_locally(1) {
-                        match t {
-                            Token::Neg => {
-                                self.consume();
-                                FToken::Mcr(i, self.get_tuple()?)
-                            },
-                            Token::LParen => FToken::Fnc(i, self.get_tuple()?),
-                            _ => FToken::Scr(i)
-                        }
-                    } else {
-                        FToken::Scr(i)
-                    },
-                    // Arithmetic operations
-                    Token::Add => FToken::Oper(Oper::Arithmetic(Arithmetic::Add)),
-                    Token::Rem => FToken::Oper(Oper::Arithmetic(Arithmetic::Rem)),
-                    Token::Mul => FToken::Oper(Oper::Arithmetic(Arithmetic::Mul)),
-                    Token::Div => FToken::Oper(Oper::Arithmetic(Arithmetic::Div)),
-                    Token::Sur => FToken::Oper(Oper::Arithmetic(Arithmetic::Sur)),
-                    
-                    // Logical operations
-                    Token::And => FToken::Oper(Oper::Logical(Logical::And)),
-                    Token::Or  => FToken::Oper(Oper::Logical(Logical::Or)),
-                    Token::Neg => FToken::Oper(Oper::Logical(Logical::Not)),
+        // A closing parenthesis without its opening one belongs to an outer tuple
+        let mut depth = 0;
+        while let Some(t) = self.peek() {
+            let start = self.peek_span();
+            // A sign is unary where an operand is expected, e.g. `-a`, `1 * -a` or `(+a)`
+            let expects_operand = match queue.last().map(|t:&Spanned<FToken>| &t.inner) {
+                None | Some(FToken::LParen) => true,
+                // A cast has been given its operand, e.g. `a as int - 1`
+                Some(FToken::Oper(o)) => !o.is_postfix(),
+                _ => false
+            };
+            let found = match t {
+                Token::Int(i) => FToken::Int(*i),
+                Token::Flt(f) => FToken::Flt(*f),
+                Token::Bln(b) => FToken::Bln(*b),
+                Token::Str(s) => FToken::Str(s.clone()),
+                t if t.to_type().is_some() => {
+                    let datatype = self.get_type()?;
+                    queue.push(Spanned::new(FToken::Type(datatype), self.span_from(start)));
+                    continue;
+                },
+                Token::Ident(i) => {
+                    let i = i.clone();
+                    match self.get_locally(1) {
+                        Some(Token::Neg) if matches!(self.get_locally(2), Some(Token::LParen)) => {
+                            self.consume();
+                            self.consume();
+                            let tuple = self.get_tuple()?;
+                            queue.push(Spanned::new(FToken::Mcr(i, tuple), self.span_from(start)));
+                            continue;
+                        },
+                        Some(Token::LParen) => {
+                            self.consume();
+                            let tuple = self.get_tuple()?;
+                            queue.push(Spanned::new(FToken::Fnc(i, tuple), self.span_from(start)));
+                            continue;
+                        },
+                        _ => FToken::Scr(i)
+                    }
+                },
+                // Unary operations
+                Token::Add if expects_operand => FToken::Oper(Oper::Unary(Unary::Pos)),
+                Token::Rem if expects_operand => FToken::Oper(Oper::Unary(Unary::Neg)),
+                Token::Neg => FToken::Oper(Oper::Unary(Unary::Not)),
+                // A cast is followed by its type, e.g. `a as float<2>`
+                Token::As => {
+                    self.consume();
+                    let datatype = self.get_type()?;
+                    queue.push(Spanned::new(FToken::Oper(Oper::Unary(Unary::Cast(datatype))), self.span_from(start)));
+                    continue;
+                },
+
+                // Arithmetic operations
+                Token::Add => FToken::Oper(Oper::Arithmetic(Arithmetic::Add)),
+                Token::Rem => FToken::Oper(Oper::Arithmetic(Arithmetic::Rem)),
+                Token::Mul => FToken::Oper(Oper::Arithmetic(Arithmetic::Mul)),
+                Token::Div => FToken::Oper(Oper::Arithmetic(Arithmetic::Div)),
+                Token::Sur => FToken::Oper(Oper::Arithmetic(Arithmetic::Sur)),
 
-                    // Comparisons
-                    Token::Gt  => FToken::Oper(Oper::Comparison(Comparison::Gt)),
-                    Token::Lt  => FToken::Oper(Oper::Comparison(Comparison::Lt)),
-                    Token::LEt => FToken::Oper(Oper::Comparison(Comparison::Le)),
-                    Token::REt => FToken::Oper(Oper::Comparison(Comparison::Ge)),
-                    Token::Eq  => FToken::Oper(Oper::Comparison(Comparison::Eq)),
-                    Token::NEq => FToken::Oper(Oper::Comparison(Comparison::Neq)),
+                // Logical operations
+                Token::And => FToken::Oper(Oper::Logical(Logical::And)),
+                Token::Or  => FToken::Oper(Oper::Logical(Logical::Or)),
 
-                    // Parentheses
-                    Token::LParen => FToken::LParen,
-                    Token::RParen => FToken::RParen,
-                    _ => break
+                // Comparisons
+                Token::Gt  => FToken::Oper(Oper::Comparison(Comparison::Gt)),
+                Token::Lt  => FToken::Oper(Oper::Comparison(Comparison::Lt)),
+                Token::LEt => FToken::Oper(Oper::Comparison(Comparison::Le)),
+                Token::REt => FToken::Oper(Oper::Comparison(Comparison::Ge)),
+                Token::Eq  => FToken::Oper(Oper::Comparison(Comparison::Eq)),
+                Token::NEq => FToken::Oper(Oper::Comparison(Comparison::Neq)),
+
+                // Parentheses
+                Token::LParen => {
+                    depth += 1;
+                    FToken::LParen
+                },
+                Token::RParen => {
+                    if depth == 0 {
+                        break;
+                    }
+                    depth -= 1;
+                    FToken::RParen
                 },
-                None => break
+                _ => break
             };
-            queue.push(found);
+            self.consume();
+            queue.push(Spanned::new(found, start));
+        }
+        let span = match (queue.first(), queue.last()) {
+            (Some(first), Some(last)) => first.span.to(&last.span),
+            _ => formula_start
+        };
+        Ok(Formula::new(queue, span))
+    }
+    fn expect_semicolon(&mut self) -> Result<(), SyntaxError> {
+        if !matches!(self.expect()?, Token::Semicolon) {
+            return Err(self.expect_token_err("`;`"));
         }
-        Ok(queue)
+        Ok(())
     }
     fn get_let(&mut self) -> Result<VariableDefinement, SyntaxError> {
-        let syntax_error = SyntaxError::InvalidFormAs("let statement".to_string());
+        let start = self.peek_span();
         if !matches!(self.expect()?, Token::Let) {
-            return Err(SyntaxError::ExpectedAToken("let keyword".to_string()));
+            return Err(self.expect_token_err("`let`"));
         }
         let identifier = if let Token::Ident(s) = self.expect()? {
             s
         } else {
-            return Err(SyntaxError::ExpectedAToken("identifier".to_string()));
+            return Err(self.expect_token_err("identifier"));
         };
         let initialise;
         let datatype;
 
         match self.expect()? {
             Token::Colon => {
-                datatype = Some(
-                    self
-                        .consume()
-                        .ok_or(SyntaxError::TokenEndsUnexpectedly)?
-                        .to_type()
-                        .ok_or(SyntaxError::ExpectedAToken("data type".to_string()))?
-                );
-                initialise = if let Token::Asn = self.expect()? {
-                    Some(ast::Assignment {
-                        lhs : identifier.clone(),
-                        rhs : Formula::from(self.get_formula()?).to_rpn()?
-                    })
-                } else {
-                    None
+                datatype = Some(self.get_type()?);
+                initialise = match self.expect()? {
+                    Token::Asn => {
+                        let rhs = self.get_formula()?.into_rpn()?;
+                        self.expect_semicolon()?;
+                        Some(ast::Assignment {
+                            lhs : identifier.clone(),
+                            rhs,
+                            span : self.span_from(start)
+                        })
+                    },
+                    Token::Semicolon => None,
+                    _ => {return Err(SyntaxError::InvalidFormAs("let statement".to_string(), self.last_span));}
                 };
             },
             Token::Asn => {
-                let rhs = Formula::from(self.get_formula()?).to_rpn()?;
+                let rhs = self.get_formula()?.into_rpn()?;
+                self.expect_semicolon()?;
                 datatype = None;
                 initialise = Some(Assignment {
                     lhs : identifier.clone(),
-                    rhs : rhs
+                    rhs,
+                    span : self.span_from(start)
                 });
             },
-            _ => {return Err(syntax_error);}
+            _ => {return Err(SyntaxError::InvalidFormAs("let statement".to_string(), self.last_span));}
         }
         let var_definement = VariableDefinement {
-            identifier : identifier,
-            datatype : datatype,
-            initialise : initialise
+            identifier,
+            datatype,
+            initialise,
+            span : self.span_from(start)
         };
         Ok(var_definement)
     }
     fn get_if(&mut self) -> Result<IfSyntax, SyntaxError> {
+        let start = self.peek_span();
         if !matches!(self.expect()?, Token::If) {
-            return Err(expect_token_err("if keyword"));
+            return Err(self.expect_token_err("`if`"));
         }
-        let conditon = Formula::from(self.get_formula()?).to_rpn()?;
+        let conditon = self.get_formula()?.into_rpn()?;
         let inside = self.get_block()?;
+        // "else if" is an else block which consists of only an if syntax
+        let else_block = if let Some(Token::Else) = self.peek() {
+            self.consume();
+            let else_start = self.peek_span();
+            Some(match self.peek() {
+                Some(Token::If) => {
+                    let else_if = self.get_if()?;
+                    CodeBlock { inside: vec![Ast::IfSyntax(else_if)], span: self.span_from(else_start) }
+                },
+                _ => self.get_block()?
+            })
+        } else {
+            None
+        };
         Ok(IfSyntax {
             condition: conditon,
-            block: inside
+            block: inside,
+            else_block,
+            span: self.span_from(start)
         })
     }
-    fn get_while(&mut self) -> Result<WhileSyntax, SyntaxError> {
+    fn get_while(&mut self, label:Option<String>) -> Result<WhileSyntax, SyntaxError> {
+        let start = self.peek_span();
         if !matches!(self.expect()?, Token::While) {
-            return Err(expect_token_err("while keyword"));
+            return Err(self.expect_token_err("`while`"));
         }
-        let conditon = Formula::from(self.get_formula()?).to_rpn()?;
+        let conditon = self.get_formula()?.into_rpn()?;
         let inside = self.get_block()?;
         Ok(WhileSyntax {
+            label,
             condition: conditon,
-            block: inside
+            block: inside,
+            span: self.span_from(start)
+        })
+    }
+    fn get_for(&mut self, label:Option<String>) -> Result<ForSyntax, SyntaxError> {
+        let start = self.peek_span();
+        if !matches!(self.expect()?, Token::For) {
+            return Err(self.expect_token_err("`for`"));
+        }
+        let variable = if let Token::Ident(s) = self.expect()? {
+            s
+        } else {
+            return Err(self.expect_token_err("loop variable"));
+        };
+        if !matches!(self.expect()?, Token::In) {
+            return Err(self.expect_token_err("`in`"));
+        }
+        let from = self.get_formula()?.into_rpn()?;
+        let inclusive = match self.expect()? {
+            Token::DotDot => false,
+            Token::DotDotEq => true,
+            _ => return Err(self.expect_token_err("`..` or `..=`"))
+        };
+        let to = self.get_formula()?.into_rpn()?;
+        // The step has to be known on compiling, since it decides which way the loop goes
+        let step = if matches!(self.peek(), Some(Token::Step)) {
+            self.consume();
+            let sign = if matches!(self.peek(), Some(Token::Rem)) {
+                self.consume();
+                -1
+            } else {
+                1
+            };
+            match self.expect()? {
+                Token::Int(i) if i != 0 => sign * i,
+                _ => return Err(self.expect_token_err("non-zero integer step"))
+            }
+        } else {
+            1
+        };
+        let inside = self.get_block()?;
+        Ok(ForSyntax {
+            label,
+            variable,
+            start: from,
+            end: to,
+            inclusive,
+            step,
+            block: inside,
+            span: self.span_from(start)
         })
     }
     pub fn get_block(&mut self) -> Result<CodeBlock, SyntaxError> {
+        let start = self.peek_span();
         let mut insides = Vec::new();
         if !matches!(self.expect()?, Token::LBrace) {
-            return Err(expect_token_err("{"));
+            return Err(self.expect_token_err("`{`"));
         }
         loop {
-            let top_token = self.peek().ok_or(SyntaxError::UnbalancedBraces)?;
+            let top_token = self.peek().ok_or(SyntaxError::UnbalancedBraces(start))?;
             let found = match top_token {
-                Token::If => AST::IfSyntax(self.get_if()?),
-                Token::While => AST::WhileSyntax(self.get_while()?),
-                Token::Let => AST::LetStatement(self.get_let()?),
-                Token::LBrace => AST::CodeBlock(self.get_block()?),
+                Token::If => Ast::IfSyntax(self.get_if()?),
+                Token::While => Ast::WhileSyntax(self.get_while(None)?),
+                Token::For => Ast::ForSyntax(self.get_for(None)?),
+                // 'label: while ... or 'label: for ...
+                Token::Label(l) => {
+                    let label = Some(l.clone());
+                    self.consume();
+                    if !matches!(self.expect()?, Token::Colon) {
+                        return Err(self.expect_token_err("`:`"));
+                    }
+                    match self.peek() {
+                        Some(Token::While) => Ast::WhileSyntax(self.get_while(label)?),
+                        Some(Token::For) => Ast::ForSyntax(self.get_for(label)?),
+                        _ => {
+                            self.expect()?;
+                            return Err(self.expect_token_err("`while` or `for`"));
+                        }
+                    }
+                },
+                Token::Break | Token::Continue => Ast::Jump(self.get_jump()?),
+                Token::Let => Ast::LetStatement(self.get_let()?),
+                Token::Fn => Ast::FunctionDefinement(self.get_fn()?),
+                Token::Return => Ast::Return(self.get_return()?),
+                Token::LBrace => Ast::CodeBlock(self.get_block()?),
                 Token::RBrace => {
-                    break Ok(CodeBlock { inside: insides });
+                    self.consume();
+                    break Ok(CodeBlock { inside: insides, span: self.span_from(start) });
                 },
-                Token::Int(_) | Token::Bln(_) | Token::Flt(_) => AST::Formula(
-                    Formula::from(self.get_formula()?)
+                Token::Int(_) | Token::Bln(_) | Token::Flt(_) | Token::LParen => Ast::Formula(
+                    self.get_formula_statement()?
                 ),
                 // identifier = ... or identifier [+-*/%] value ...
                 Token::Ident(_) => if let Some(Token::Asn) = self.get_locally(1) {
-                    AST::Assignment(self.get_assignment()?)
+                    Ast::Assignment(self.get_assignment()?)
                 } else {
-                    AST::Formula(Formula::from(self.get_formula()?))
+                    Ast::Formula(self.get_formula_statement()?)
                 },
-                _ => return Err(SyntaxError::ALineMustntStartWith(top_token.clone()))
+                _ => return Err(SyntaxError::ALineMustntStartWith(top_token.clone(), self.peek_span()))
             };
             insides.push(found);
         }
     }
     fn get_assignment(&mut self) -> Result<Assignment, SyntaxError> {
-        let syntax_error = SyntaxError::InvalidFormAs("assignment".to_string());
+        let start = self.peek_span();
         let lhs = if let (Some(Token::Ident(s)), Some(Token::Asn)) = (self.consume(), self.consume()) {
             s
         } else {
-            return Err(syntax_error);
+            return Err(SyntaxError::InvalidFormAs("assignment".to_string(), self.span_from(start)));
         };
-        let rhs = ast::Formula::from(self.get_formula()?).to_rpn()?;
-        if !matches!(self.consume(), Some(Token::Semicolon)) {
-            return Err(expect_token_err("semicolon"));
-        }
+        let rhs = self.get_formula()?.into_rpn()?;
+        self.expect_semicolon()?;
         Ok(ast::Assignment {
-            lhs : lhs,
-            rhs : rhs
+            lhs,
+            rhs,
+            span : self.span_from(start)
         })
     }
+    fn get_formula_statement(&mut self) -> Result<Formula, SyntaxError> {
+        let formula = self.get_formula()?;
+        self.expect_semicolon()?;
+        Ok(formula)
+    }
+    fn get_type(&mut self) -> Result<Type, SyntaxError> {
+        let datatype = self
+            .expect()?
+            .to_type()
+            .ok_or(self.expect_token_err("data type"))?;
+        // `float<N>` has N decimal places
+        if !matches!(datatype, Type::Float(_)) || !matches!(self.peek(), Some(Token::Lt)) {
+            return Ok(datatype);
+        }
+        self.consume();
+        let places = match self.expect()? {
+            Token::Int(i) if (0..=MAX_FLOAT_PLACES as i32).contains(&i) => i as u32,
+            _ => return Err(self.expect_token_err(&format!("decimal places from 0 to {}", MAX_FLOAT_PLACES)))
+        };
+        if !matches!(self.expect()?, Token::Gt) {
+            return Err(self.expect_token_err("`>`"));
+        }
+        Ok(Type::Float(places))
+    }
+    fn get_fn(&mut self) -> Result<FunctionDefinement, SyntaxError> {
+        let start = self.peek_span();
+        if !matches!(self.expect()?, Token::Fn) {
+            return Err(self.expect_token_err("`fn`"));
+        }
+        let func_name = if let Token::Ident(s) = self.expect()? {
+            s
+        } else {
+            return Err(self.expect_token_err("function name"));
+        };
+        if !matches!(self.expect()?, Token::LParen) {
+            return Err(self.expect_token_err("`(`"));
+        }
+        let mut arguments = Vec::new();
+        if let Some(Token::RParen) = self.peek() {
+            self.consume();
+        } else {
+            loop {
+                let argument_start = self.peek_span();
+                let name = if let Token::Ident(s) = self.expect()? {
+                    s
+                } else {
+                    return Err(self.expect_token_err("argument name"));
+                };
+                if !matches!(self.expect()?, Token::Colon) {
+                    return Err(self.expect_token_err("`:`"));
+                }
+                arguments.push(Arguments {
+                    name,
+                    datatype : self.get_type()?,
+                    span : self.span_from(argument_start)
+                });
+                match self.expect()? {
+                    Token::RParen => {break;},
+                    Token::Comma => {continue;},
+                    _ => {return Err(SyntaxError::InvalidFormAs("function definement".to_string(), self.last_span));}
+                }
+            }
+        }
+        let datatype = if let Some(Token::Arr) = self.peek() {
+            self.consume();
+            self.get_type()?
+        } else {
+            Type::None
+        };
+        // The span of a function is its signature, not to point the whole body
+        let span = self.span_from(start);
+        Ok(FunctionDefinement {
+            func_name,
+            datatype,
+            arguments,
+            block : self.get_block()?,
+            span
+        })
+    }
+    fn get_return(&mut self) -> Result<ReturnStatement, SyntaxError> {
+        let start = self.peek_span();
+        if !matches!(self.expect()?, Token::Return) {
+            return Err(self.expect_token_err("`return`"));
+        }
+        let value = if let Some(Token::Semicolon) = self.peek() {
+            None
+        } else {
+            Some(self.get_formula()?.into_rpn()?)
+        };
+        self.expect_semicolon()?;
+        Ok(ReturnStatement { value, span : self.span_from(start) })
+    }
+    fn get_jump(&mut self) -> Result<JumpStatement, SyntaxError> {
+        let start = self.peek_span();
+        let is_continue = match self.expect()? {
+            Token::Break => false,
+            Token::Continue => true,
+            _ => return Err(self.expect_token_err("`break` or `continue`"))
+        };
+        let label = if let Some(Token::Label(l)) = self.peek() {
+            let label = l.clone();
+            self.consume();
+            Some(label)
+        } else {
+            None
+        };
+        self.expect_semicolon()?;
+        Ok(JumpStatement { is_continue, label, span: self.span_from(start) })
+    }
 }
\ No newline at end of file
@@ -0,0 +1,234 @@
+use super::ast::SyntaxError;
+use super::tokeniser::LexError;
+use super::{CompileError, CompileErrorKind, Span};
+
+/// A message pointing a part of the source
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String
+}
+impl Label {
+    fn new(span:Span, message:String) -> Self {
+        Label { span, message }
+    }
+}
+
+/// An error report which can be rendered with the source it was raised from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// A stable code such as `E0012`, which is never reused for another error
+    pub code: &'static str,
+    pub message: String,
+    pub primary: Label,
+    pub secondary: Vec<Label>
+}
+
+impl From<&LexError> for Diagnostic {
+    fn from(value: &LexError) -> Self {
+        let (code, label) = match value {
+            LexError::UnclosedString(_) => ("E0201", "unclosed string"),
+            LexError::UnclosedBlockComment(_) => ("E0202", "unclosed comment"),
+            LexError::MalformedNumber(_, _) => ("E0203", "invalid number"),
+            LexError::IntegerOverflow(_, _) => ("E0204", "out of the range of int"),
+            LexError::StrayDollar(_) => ("E0205", "expected an identifier after this"),
+            LexError::UnknownCharacter(_, _) => ("E0206", "unknown character")
+        };
+        Diagnostic {
+            code,
+            message: value.message(),
+            primary: Label::new(value.span(), label.to_string()),
+            secondary: Vec::new()
+        }
+    }
+}
+impl From<&SyntaxError> for Diagnostic {
+    fn from(value: &SyntaxError) -> Self {
+        let (code, label) = match value {
+            SyntaxError::ExpectedAToken(_, _) => ("E0001", "unexpected token"),
+            SyntaxError::EmptyFormula(_) => ("E0002", "expected an expression"),
+            SyntaxError::InvalidFormAs(_, _) => ("E0003", "invalid form"),
+            SyntaxError::UndefinedOperationFound(_, _, _, _) => ("E0004", "undefined operation"),
+            SyntaxError::NotEnoughOperand(_) => ("E0005", "missing operand"),
+            SyntaxError::OperatorAtInvalidPosition(_, _) => ("E0006", "unexpected operator"),
+            SyntaxError::UnbalancedBraces(_) => ("E0007", "unclosed brace"),
+            SyntaxError::UnbalancedParentheses(_) => ("E0008", "unbalanced parenthesis"),
+            SyntaxError::TokenEndsUnexpectedly(_) => ("E0009", "expected more tokens"),
+            SyntaxError::ALineMustntStartWith(_, _) => ("E0010", "unexpected token"),
+            SyntaxError::InvalidTokenInAFormula(_, _) => ("E0011", "not allowed in a formula"),
+            SyntaxError::ArgumentCountMismatch(_) => ("E0012", "wrong number of arguments")
+        };
+        Diagnostic {
+            code,
+            message: value.message(),
+            primary: Label::new(value.span(), label.to_string()),
+            secondary: Vec::new()
+        }
+    }
+}
+impl From<&CompileError> for Diagnostic {
+    fn from(value: &CompileError) -> Self {
+        let mut secondary = Vec::new();
+        let (code, label) = match &value.inner {
+            CompileErrorKind::LexicalErrorsOccured(e) if !e.is_empty() => return Diagnostic::from(&e[0]),
+            CompileErrorKind::LexicalErrorsOccured(_) => ("E0200", String::new()),
+            CompileErrorKind::ASyntaxErrorOccured(e) => return Diagnostic::from(e),
+            CompileErrorKind::InvalidTokenInAFormula(_) => ("E0101", "not allowed in a formula".to_string()),
+            CompileErrorKind::EmptyFormulaGiven => ("E0102", "expected an expression".to_string()),
+            CompileErrorKind::UndefinedIdentifierReferenced(_) => ("E0103", "not found in this scope".to_string()),
+            CompileErrorKind::UnknownTypeSpecialised(_) => ("E0104", "expected a type".to_string()),
+            CompileErrorKind::LHSDoesntSatisfyValidFormat => ("E0105", "invalid left hand side".to_string()),
+            CompileErrorKind::InvalidRHS(t) => ("E0106", format!("`{}` can't be stored here", t)),
+            CompileErrorKind::TheTokenIsntValue(_) => ("E0107", "expected a value".to_string()),
+            CompileErrorKind::InvalidFormulaStructure(_) => ("E0108", "invalid formula".to_string()),
+            CompileErrorKind::UnsupportedLiteralType(_) => ("E0109", "unsupported literal".to_string()),
+            CompileErrorKind::UndefinedOperation(l, o, r) => ("E0110", format!("no `{}` between `{}` and `{}`", o, l, r)),
+            CompileErrorKind::UnbalancedParentheses => ("E0111", "unbalanced parenthesis".to_string()),
+            CompileErrorKind::TheTypeOfAIndentifierWontBeConfirmed(_) => ("E0112", "type annotation needed".to_string()),
+            CompileErrorKind::ArgumentCountMismatch(n, e, g, defined) => {
+                secondary.push(Label::new(*defined, format!("`{}` is defined here with {} parameter{}", n, e, plural(*e))));
+                ("E0113", format!("{} argument{} given", g, plural(*g)))
+            },
+            CompileErrorKind::ReturnOutsideOfFunction => ("E0114", "not inside any function".to_string()),
+            CompileErrorKind::MismatchedAssignment(n, e, g, declared) => {
+                secondary.push(Label::new(*declared, format!("`{}` is declared here as `{}`", n, e)));
+                ("E0115", format!("expected `{}`, found `{}`", e, g))
+            },
+            CompileErrorKind::UndefinedUnaryOperation(o, t) => ("E0116", format!("no `{}` for `{}`", o, t)),
+            CompileErrorKind::FloatOutOfRange(_, t) => ("E0117", format!("doesn't fit in a score as `{}`", t)),
+            CompileErrorKind::ImplicitNarrowing(g, e) => ("E0118", format!("expected `{}`, found `{}`", e, g)),
+            CompileErrorKind::JumpOutsideOfLoop(_) => ("E0119", "not inside any loop".to_string()),
+            CompileErrorKind::UndefinedLabel(_) => ("E0120", "no loop has this label".to_string()),
+            CompileErrorKind::UnsupportedByTarget(_, _, t) => ("E0121", format!("not available in Minecraft {}", t)),
+            CompileErrorKind::FunctionRedefined(n, previous) => {
+                secondary.push(Label::new(*previous, format!("the previous definition of `{}` is here", n)));
+                ("E0122", format!("`{}` redefined here", n))
+            },
+            CompileErrorKind::ReservedFunctionName(_) => ("E0123", "reserved name".to_string()),
+            CompileErrorKind::UnjoinableString => ("E0124", "has `\"` or `\\`".to_string()),
+            CompileErrorKind::NonTailRecursion(_) => ("E0125", "recursive call outside of `return`".to_string()),
+            CompileErrorKind::MissingReturnValue(t) => ("E0126", format!("expected a value of `{}`", t)),
+            CompileErrorKind::MissingReturn(_, t) => ("E0127", format!("expected `{}` returned on every path", t))
+        };
+        Diagnostic {
+            code,
+            message: value.message(),
+            primary: Label::new(value.span, label),
+            // A label which doesn't know where it is can't be rendered
+            secondary: secondary.into_iter().filter(|l| !l.span.is_unknown()).collect()
+        }
+    }
+}
+
+fn plural(count:usize) -> &'static str {
+    if count == 1 {""} else {"s"}
+}
+
+#[test]
+fn render_test() {
+    let source = "let a = true;\nlet b = 1;\n\nfn f(x:int) {}\nf(1, 2);\na = b + 1;";
+    let error = crate::compile(source).unwrap_err();
+    assert_eq!(
+        Diagnostic::from(&error).render(source, "main.mcpp"),
+        [
+            "error[E0113]: the function `f` takes 1 argument but 2 were given",
+            " --> main.mcpp:5:2",
+            "  |",
+            "4 | fn f(x:int) {}",
+            "  | ----------- `f` is defined here with 1 parameter",
+            "5 | f(1, 2);",
+            "  |  ^^^^^^ 2 arguments given",
+        ].join("\n")
+    );
+    let source = "let a = true;\nlet b = 1;\n\n\na = b + 1;";
+    let diagnostic = Diagnostic::from(&crate::compile(source).unwrap_err());
+    assert_eq!(diagnostic.code, "E0115");
+    let lexical = "let a = 1 @ 2;\nlet b = 99999999999;";
+    let codes = Diagnostic::all(&crate::compile(lexical).unwrap_err())
+        .iter()
+        .map(|d| d.code)
+        .collect::<Vec<&str>>();
+    assert_eq!(codes, ["E0206", "E0204"]);
+    // The brace left open is pointed, not the block wrapping the whole source
+    let unclosed = "fn f() {\n    if true {}\nlet b = 1;";
+    assert_eq!(
+        Diagnostic::from(&crate::compile(unclosed).unwrap_err()).render(unclosed, "main.mcpp"),
+        [
+            "error[E0007]: this brace is never closed",
+            " --> main.mcpp:1:8",
+            "  |",
+            "1 | fn f() {",
+            "  |        ^ unclosed brace",
+        ].join("\n")
+    );
+    assert_eq!(
+        diagnostic.render(source, "main.mcpp"),
+        [
+            "error[E0115]: mismatched types: `a` is `bool` but `int` was given",
+            " --> main.mcpp:5:1",
+            "  |",
+            "1 | let a = true;",
+            "  | ------------- `a` is declared here as `bool`",
+            "...",
+            "5 | a = b + 1;",
+            "  | ^^^^^^^^^^ expected `bool`, found `int`",
+        ].join("\n")
+    );
+}
+
+impl Diagnostic {
+    /// Every diagnostic an error has, since the tokeniser reports all the errors it found at once
+    pub fn all(error:&CompileError) -> Vec<Diagnostic> {
+        match &error.inner {
+            CompileErrorKind::LexicalErrorsOccured(e) if !e.is_empty() => e.iter().map(Diagnostic::from).collect(),
+            _ => vec![Diagnostic::from(error)]
+        }
+    }
+    /// Renders the diagnostic with the lines of the source it points.
+    /// The origin is the name of the source shown with the location.
+    pub fn render(&self, source:&str, origin:&str) -> String {
+        let mut result = vec![format!("error[{}]: {}", self.code, self.message)];
+        if self.primary.span.is_unknown() {
+            return result.join("\n");
+        }
+        result.push(format!(" --> {}:{}", origin, self.primary.span));
+
+        let mut labels = vec![(&self.primary, '^')];
+        labels.extend(self.secondary.iter().map(|l| (l, '-')));
+        labels.sort_by_key(|(l, _)| (l.span.line, l.span.column));
+
+        let lines = source.split('\n').collect::<Vec<&str>>();
+        let gutter = labels.iter().map(|(l, _)| l.span.line).max().unwrap_or(0).to_string().len();
+        let empty_gutter = " ".repeat(gutter);
+        result.push(format!("{} |", empty_gutter));
+
+        let mut last_line = None;
+        for (label, marker) in labels {
+            let line = label.span.line;
+            let text = lines.get(line - 1).copied().unwrap_or("");
+            if last_line != Some(line) {
+                if last_line.is_some_and(|l| l + 1 < line) {
+                    result.push("...".to_string());
+                }
+                result.push(format!("{:>gutter$} | {}", line, text, gutter = gutter));
+                last_line = Some(line);
+            }
+            // A span across lines is underlined until the end of its first line
+            let line_start = lines.iter().take(line - 1).map(|l| l.len() + 1).sum::<usize>();
+            let line_end = line_start + text.len();
+            let underlined = source
+                .get(label.span.start..label.span.end.min(line_end))
+                .map(|s| s.chars().count())
+                .unwrap_or(0)
+                .max(1);
+            result.push(format!(
+                "{} | {}{} {}",
+                empty_gutter,
+                " ".repeat(label.span.column - 1),
+                marker.to_string().repeat(underlined),
+                label.message
+            ).trim_end().to_string());
+        }
+        result.join("\n")
+    }
+}
@@ -5,18 +5,41 @@ use crate::compiler::ast::serialiser::IToken;
 
 use scoreboard::command_ast::CommandAST;
 pub use scoreboard::Scoreboard;
+use scoreboard::unary_operation::Unary;
+use scoreboard::FLOAT_PLACES;
 
+/// A float is a fixed-point number, which has the number of its decimal places
 #[derive(Debug, Clone, Copy, PartialEq)]
-pub enum Type { Int, Float, Bool, Str, None }
+pub enum Type { Int, Float(u32), Bool, Str, None }
+/// The keyword of the type as it's written in the source
 impl std::fmt::Display for Type {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", match self {
-            Type::None => "None",
-            Type::Bool => "Bool",
-            Type::Float => "Float",
-            Type::Int => "Int",
-            Type::Str => "Str"
-        })
+        match self {
+            Type::None => write!(f, "none"),
+            Type::Bool => write!(f, "bool"),
+            Type::Float(p) if *p == FLOAT_PLACES => write!(f, "float"),
+            Type::Float(p) => write!(f, "float<{}>", p),
+            Type::Int => write!(f, "int"),
+            Type::Str => write!(f, "str")
+        }
+    }
+}
+impl Type {
+    /// `float` without its decimal places
+    pub const FLOAT:Type = Type::Float(FLOAT_PLACES);
+    /// The decimal places of the score, which is 0 for an int
+    pub fn places(&self) -> u32 {
+        match self {
+            Type::Float(p) => *p,
+            _ => 0
+        }
+    }
+    pub fn is_number(&self) -> bool {
+        matches!(self, Type::Int | Type::Float(_))
+    }
+    /// Whether some digits are dropped when it's assigned to the type, e.g. a float to an int
+    pub fn narrows_to(&self, to:&Type) -> bool {
+        self.is_number() && to.is_number() && self.places() > to.places()
     }
 }
 pub trait Operator {
@@ -29,30 +52,62 @@ pub trait Operator {
 pub enum Oper {
     Arithmetic(scoreboard::arithmetic_operation::Arithmetic),
     Logical(scoreboard::logical_operation::Logical),
-    Comparison(scoreboard::comparison_operation::Comparison)
+    Comparison(scoreboard::comparison_operation::Comparison),
+    Unary(scoreboard::unary_operation::Unary)
 }
 impl std::fmt::Display for Oper {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", match self {
-            Self::Arithmetic(a) => a.to_str(),
-            Self::Comparison(c) => c.to_str(),
-            Self::Logical(l) => l.to_str()
-        })
+        match self {
+            Self::Unary(Unary::Cast(t)) => write!(f, "as {}", t),
+            _ => write!(f, "{}", self.to_str())
+        }
     }
 }
 impl Oper {
+    /// The higher one binds tighter, as in C.
+    ///
+    /// | Priority | Operators | Associativity |
+    /// | ---- | ---- | ---- |
+    /// | 6 | `!` `-` `+` (unary), `as` (postfix) | right |
+    /// | 5 | `*` `/` `%` | left |
+    /// | 4 | `+` `-` | left |
+    /// | 3 | `<` `<=` `>` `>=` `==` `!=` | left |
+    /// | 2 | `&` | left |
+    /// | 1 | `\|` | left |
     pub fn get_priority(&self) -> u32 {
         match self {
             Oper::Arithmetic(o) => o.get_priority(),
             Oper::Comparison(o) => o.get_priority(),
             Oper::Logical(o) => o.get_priority(),
+            Oper::Unary(o) => o.get_priority(),
+        }
+    }
+    pub fn get_type(&self, left:&Type, right:&Type) -> Option<Type> {
+        match self {
+            Oper::Arithmetic(o) => o.get_type(left, right),
+            Oper::Comparison(o) => o.get_type(left, right),
+            Oper::Logical(o) => o.get_type(left, right),
+            // A unary operator only has the right operand
+            Oper::Unary(o) => o.get_type(right),
         }
     }
+    pub fn is_unary(&self) -> bool {
+        matches!(self, Oper::Unary(_))
+    }
+    /// A cast follows its operand, e.g. `a as int`
+    pub fn is_postfix(&self) -> bool {
+        matches!(self, Oper::Unary(u) if u.is_postfix())
+    }
+    /// `a - b - c` is `(a - b) - c`, while `- - a` is `-(-a)`
+    pub fn is_left_associative(&self) -> bool {
+        !self.is_unary()
+    }
     pub fn to_str(&self) -> &str {
         match self {
             Oper::Arithmetic(o) => o.to_str(),
             Oper::Comparison(o) => o.to_str(),
             Oper::Logical(o) => o.to_str(),
+            Oper::Unary(o) => o.to_str(),
         }
     }
 }
\ No newline at end of file
@@ -1,17 +1,50 @@
 pub mod arithmetic_operation;
 pub mod comparison_operation;
 pub mod logical_operation;
+pub mod unary_operation;
 pub mod command_ast;
 
 use command_ast::{FormulaConstructer, CommandAST};
-use rand::Rng;
 use super::Type;
-use super::CompileError;
+use crate::compiler::{CompileError, CompileErrorKind, Span};
 use crate::compiler::ast::serialiser::IToken;
 
 pub const NAMESPACE:&str = "MCPP.var";
-pub const FLOAT_MAGNIFICATION:i32 = 1000;
-pub const TEMP_ID_LEN:u32 = 16;
+/// The decimal places of `float` without `<N>`
+pub const FLOAT_PLACES:u32 = 3;
+/// More places than this overflow a score even for 1.0
+pub const MAX_FLOAT_PLACES:u32 = 9;
+pub const FLOAT_MAGNIFICATION:i32 = magnification(FLOAT_PLACES);
+/// The data storage of strings, which is `<namespace>:vars`
+pub const STORAGE:&str = "vars";
+
+/// How many times a float of the decimal places is multiplied in its score
+pub const fn magnification(places:u32) -> i32 {
+    10_i32.pow(places)
+}
+/// The fixed-point score of a float, rounded so that `0.29` isn't stored as `289`.
+/// It's an error if the value doesn't fit in a score at the decimal places.
+pub fn fixed_point(value:f64, places:u32) -> Result<i32, CompileError> {
+    // The shortest digits of the value are the ones of the literal, which are scaled without
+    // multiplying the value, so that `1.005` isn't stored as `100` at 2 places
+    let digits = value.abs().to_string();
+    let (whole, fraction) = digits.split_once('.').unwrap_or((&digits, ""));
+    let fraction = format!("{:0<width$}", fraction, width = places as usize + 1);
+    let (kept, next) = fraction.split_at(places as usize);
+    format!("{}{}", whole, kept)
+        .parse::<i64>()
+        .ok()
+        .map(|scaled| scaled + (next.as_bytes()[0] >= b'5') as i64)
+        .and_then(|scaled| i32::try_from(if value < 0.0 {-scaled} else {scaled}).ok())
+        // Debug keeps the point of a whole number such as `100.0`
+        .ok_or(CompileErrorKind::FloatOutOfRange(format!("{:?}", value), Type::Float(places)).at(Span::default()))
+}
+/// The fixed-point score of an int, which is an error as well if it doesn't fit
+pub fn scale_int(value:i32, places:u32) -> Result<i32, CompileError> {
+    value
+        .checked_mul(magnification(places))
+        .ok_or(CompileErrorKind::FloatOutOfRange(value.to_string(), Type::Float(places)).at(Span::default()))
+}
 
 #[derive(Debug, Clone)]
 pub struct Scoreboard {
@@ -33,95 +66,121 @@ impl Scoreboard {
         let mut f_construct = FormulaConstructer::new();
         match right {
             IToken::Int(i) => match self.datatype {
+                Type::Int | Type::Float(_) => Ok(
+                    f_construct
+                        .assign_num(self, scale_int(*i, self.datatype.places())?)
+                        .build()
+                ),
+                _ => Err(CompileErrorKind::InvalidRHS(Box::new(right.clone())).at(Span::default()))
+            },
+            IToken::Flt(f) => match self.datatype {
                 Type::Int => Ok(
                     f_construct
-                        .assign_num(&self, *i)
+                        .assign_num(self, fixed_point(*f, FLOAT_PLACES)?.div_euclid(FLOAT_MAGNIFICATION))
                         .build()
                 ),
-                Type::Float => Ok(
+                Type::Float(p) => Ok(
                     f_construct
-                        .assign_num(&self, *i * FLOAT_MAGNIFICATION)
+                        .assign_num(self, fixed_point(*f, p)?)
                         .build()
                 ),
-                _ => Err(CompileError::InvalidRHS(right.clone()))
+                _ => Err(CompileErrorKind::InvalidRHS(Box::new(right.clone())).at(Span::default()))
             },
-            IToken::Flt(f) => match self.datatype {
-                Type::Int => Ok(
+            IToken::Bln(b) => match self.datatype {
+                Type::Bool => Ok(
                     f_construct
-                        .assign_num(&self, *f as i32)
+                        .assign_num(self, *b as i32)
                         .build()
                 ),
-                Type::Float => Ok(
+                _ => Err(CompileErrorKind::InvalidRHS(Box::new(right.clone())).at(Span::default()))
+            },
+            IToken::Str(s) => match self.datatype {
+                Type::Str => Ok(
                     f_construct
-                        .assign_num(&self, (*f * (FLOAT_MAGNIFICATION as f32)) as i32)
+                        .set_string(self, s)
                         .build()
                 ),
-                _ => Err(CompileError::InvalidRHS(right.clone()))
+                _ => Err(CompileErrorKind::InvalidRHS(Box::new(right.clone())).at(Span::default()))
             },
             IToken::Scr(s) => {
                 match self.datatype {
-                    Type::Int => match s.datatype {
-                        Type::Int => Ok(
+                    // The score is rescaled to the decimal places, which are 0 for an int
+                    Type::Int | Type::Float(_) if s.datatype.is_number() => Ok(
+                        f_construct
+                            .assign_score(self, s)
+                            .rescale(self, s.datatype.places(), self.datatype.places())
+                            .build()
+                    ),
+                    Type::Bool => match s.datatype {
+                        Type::Bool => Ok(
                             f_construct
-                                .assign_score(&self, s)
+                                .assign_score(self, s)
                                 .build()
                         ),
-                        Type::Float => Ok(
-                            f_construct
-                                .assign_score(&self, s)
-                                .intify(&self)
-                                .build()
-                        ),
-                        _ => Err(CompileError::InvalidRHS(right.clone()))
+                        _ => Err(CompileErrorKind::InvalidRHS(Box::new(right.clone())).at(Span::default()))
                     },
-                    Type::Float => match s.datatype {
-                        Type::Int => Ok(
-                            f_construct
-                                .assign_score(&self, s)
-                                .fltify(&self)
-                                .build()
-                        ),
-                        Type::Float => Ok(
+                    Type::Str => match s.datatype {
+                        Type::Str => Ok(
                             f_construct
-                                .assign_score(&self, s)
+                                .copy_string(self, s)
                                 .build()
                         ),
-                        _ => Err(CompileError::InvalidRHS(right.clone()))
+                        _ => Err(CompileErrorKind::InvalidRHS(Box::new(right.clone())).at(Span::default()))
                     },
-                    _ => Err(CompileError::InvalidRHS(right.clone()))
+                    _ => Err(CompileErrorKind::InvalidRHS(Box::new(right.clone())).at(Span::default()))
                 }
             },
-            _ => Err(CompileError::TheTokenIsntValue(right.clone()))
+            _ => Err(CompileErrorKind::TheTokenIsntValue(Box::new(right.clone())).at(Span::default()))
         }
     }
+    /// A value in the compound of the data storage, such as `TEMP.CONCAT.left`
+    pub fn child(&self, name:&str, datatype:Type) -> Scoreboard {
+        let mut scope = self.scope.clone();
+        scope.push(self.name.clone());
+        Scoreboard { name: name.to_string(), scope, datatype }
+    }
     pub fn free(&self) -> Vec<CommandAST> {
-        FormulaConstructer::new().free(&self).build()
+        match self.datatype {
+            Type::Str => FormulaConstructer::new().remove_string(self).build(),
+            _ => FormulaConstructer::new().free(self).build()
+        }
     }
 }
+/// It's freed in the operation it's used, so that every operation can share the same one
 pub fn get_type_adjusted_temp(datatype:Type) -> Scoreboard {
     Scoreboard {
-        name: format!("CALC_TYPE_ADJUSTED_{}", generate_random_id(16)),
+        name: "CALC_TYPE_ADJUSTED".to_string(),
         scope: vec!["TEMP".to_string()],
-        datatype: datatype
+        datatype
     }
 }
-pub fn get_calc_temp(datatype:Type) -> Scoreboard {
+/// The temps are numbered by `Compiler::next_temp_id` not to overlap each other
+pub fn get_calc_temp(datatype:Type, id:usize) -> Scoreboard {
     Scoreboard {
-        name: format!("CALC_TEMP_{}", generate_random_id(16)),
+        name: format!("CALC_TEMP_{}", id),
         scope: vec!["TEMP".to_string()],
-        datatype: datatype
+        datatype
     }
 }
-pub fn get_calc_result_temp(datatype:Type) -> Scoreboard {
+pub fn get_calc_result_temp(datatype:Type, id:usize) -> Scoreboard {
     Scoreboard {
-        name: format!("CALC_RESULT_{}", generate_random_id(16)),
+        name: format!("CALC_RESULT_{}", id),
         scope: vec!["TEMP".to_string()],
-        datatype: datatype
+        datatype
+    }
+}
+/// The compound passed to a function macro, which is freed after the call
+pub fn get_macro_arguments(name:&str) -> Scoreboard {
+    Scoreboard {
+        name: name.to_string(),
+        scope: vec!["TEMP".to_string()],
+        datatype: Type::Str
+    }
+}
+pub fn get_return_slot(scope:Vec<String>, datatype:Type) -> Scoreboard {
+    Scoreboard {
+        name: "RETURN".to_string(),
+        scope,
+        datatype
     }
 }
-pub fn generate_random_id(length:u32) -> String {
-    let mut rng = rand::rng();
-    (0..length)
-        .map(|_| rng.random_range('a'..='z') as char)
-        .collect::<String>()
-}
\ No newline at end of file
@@ -1,18 +1,39 @@
 use super::super::Operator;
 use super::command_ast::{FormulaConstructer, CommandAST};
-use super::{get_type_adjusted_temp, Scoreboard, FLOAT_MAGNIFICATION};
-use crate::compiler::CompileError;
+use super::{fixed_point, get_macro_arguments, get_type_adjusted_temp, scale_int, Scoreboard, FLOAT_PLACES};
+use crate::compiler::{CompileError, CompileErrorKind, Span};
 use crate::evaluater::{Oper, Type};
 use crate::compiler::ast::serialiser::IToken;
 
+/// The function joining two strings, which is made on its first use
+pub const CONCAT_FUNCTION:&str = "__concat";
+
+/// The arguments of the concatenation, `left`, `right` and `result`
+fn concat_arguments() -> (Scoreboard, Scoreboard, Scoreboard, Scoreboard) {
+    let arguments = get_macro_arguments("CONCAT");
+    let (left, right, result) = (
+        arguments.child("left", Type::Str),
+        arguments.child("right", Type::Str),
+        arguments.child("result", Type::Str)
+    );
+    (arguments, left, right, result)
+}
+/// The inside of the concatenation function, which has to be a function macro
+pub fn concat_macro() -> Vec<CommandAST> {
+    let (_, _, _, result) = concat_arguments();
+    FormulaConstructer::new()
+        .set_string(&result, "$(left)$(right)")
+        .build()
+}
+
 #[derive(Debug, Clone)]
 pub enum Arithmetic { Add, Rem, Mul, Div, Sur }
 
 impl Operator for Arithmetic {
     fn get_priority(&self) -> u32 {
         match self {
-            Arithmetic::Mul | Arithmetic::Div | Arithmetic::Sur => 3,
-            Arithmetic::Add | Arithmetic::Rem => 2,
+            Arithmetic::Mul | Arithmetic::Div | Arithmetic::Sur => 5,
+            Arithmetic::Add | Arithmetic::Rem => 4,
         }
     }
     fn to_str(&self) -> &str {
@@ -25,19 +46,23 @@ impl Operator for Arithmetic {
         }
     }
     fn calc(&self, left:&Scoreboard, right:&IToken) -> Result<Vec<CommandAST>, CompileError> {
+        if left.datatype == Type::Str {
+            return self.concat(left, right);
+        }
         match right {
             IToken::Scr(s) => self.calc_score(left, s),
             IToken::Int(i) => self.calc_int(left, *i),
             IToken::Flt(f) => self.calc_float(left, *f),
-            IToken::Bln(_) => Err(CompileError::UndefinedOperation(left.datatype, Oper::Arithmetic(self.clone()), Type::Bool)),
-            _ => Err(CompileError::TheTokenIsntValue(right.clone()))
+            IToken::Bln(_) => Err(CompileErrorKind::UndefinedOperation(left.datatype, Oper::Arithmetic(self.clone()), Type::Bool).at(Span::default())),
+            _ => Err(CompileErrorKind::TheTokenIsntValue(Box::new(right.clone())).at(Span::default()))
         }
     }
     fn get_type(&self, left:&Type, right:&Type) -> Option<Type> {
         match self {
+            Arithmetic::Add if (left, right) == (&Type::Str, &Type::Str) => Some(Type::Str),
             Arithmetic::Add | Arithmetic::Rem | Arithmetic::Mul | Arithmetic::Div => match (left, right) {
-                (Type::Bool, _) | (_, Type::Bool) => None,
-                _ => Some(left.clone())
+                (Type::Bool | Type::Str, _) | (_, Type::Bool | Type::Str) => None,
+                _ => Some(*left)
             },
             Arithmetic::Sur => match (left, right) {
                 (Type::Int, Type::Int) => Some(Type::Int),
@@ -47,14 +72,41 @@ impl Operator for Arithmetic {
     }
 }
 impl Arithmetic {
+    /// [Left] + [Right] of strings, which are joined by `CONCAT_FUNCTION`
+    fn concat(&self, left:&Scoreboard, right:&IToken) -> Result<Vec<CommandAST>, CompileError> {
+        let (arguments, left_argument, right_argument, result) = concat_arguments();
+        let mut f_constract = FormulaConstructer::new();
+        f_constract.copy_string(&left_argument, left);
+        match (self, right) {
+            (Arithmetic::Add, IToken::Str(s)) => f_constract.set_string(&right_argument, s),
+            (Arithmetic::Add, IToken::Scr(s)) if s.datatype == Type::Str => f_constract.copy_string(&right_argument, s),
+            _ => return Err(CompileErrorKind::UndefinedOperation(
+                left.datatype,
+                Oper::Arithmetic(self.clone()),
+                match right {
+                    IToken::Scr(s) => s.datatype,
+                    IToken::Int(_) => Type::Int,
+                    IToken::Flt(_) => Type::FLOAT,
+                    IToken::Bln(_) => Type::Bool,
+                    _ => Type::Str
+                }
+            ).at(Span::default()))
+        };
+        Ok(f_constract
+            .call_with(CONCAT_FUNCTION.to_string(), &arguments)
+            .copy_string(left, &result)
+            .remove_string(&arguments)
+            .build()
+        )
+    }
     fn calc_score(&self, left:&Scoreboard, right:&Scoreboard) -> Result<Vec<CommandAST>, CompileError> {
         let mut f_constract = FormulaConstructer::new();
         let oper_eq = format!("{}=", self.to_str());
-        let undefined_operation_occured = CompileError::UndefinedOperation(
-            left.datatype.clone(),
+        let undefined_operation_occured = CompileErrorKind::UndefinedOperation(
+            left.datatype,
             Oper::Arithmetic(self.clone()),
-            right.datatype.clone()
-        );
+            right.datatype
+        ).at(Span::default());
 
         match (left.datatype, right.datatype) {
             // [Left] [+-*/%] [Right]
@@ -63,53 +115,63 @@ impl Arithmetic {
                     .calc_score(left, oper_eq, right)
                     .build()
             ),
-            // [Left] [+-*/%] ([Right * MAG] / [MAG])
-            (Type::Int, Type::Float) => {
-                let adjust_temp = get_type_adjusted_temp(Type::Int);
-                Ok(
-                    f_constract
-                        .assign_score(left, right)
-                        .intify(&adjust_temp)
-                        .calc_score(left, oper_eq, &adjust_temp)
-                        .free(&adjust_temp)
-                        .build()
-                )
-            },
-            // [Left * MAG] [+-*/%] [Right * MAG]
-            (Type::Float, Type::Int) => {
-                let adjust_temp = get_type_adjusted_temp(Type::Int);
-                Ok(
-                    f_constract
-                        .assign_score(left, &adjust_temp)
-                        .fltify(&adjust_temp)
-                        .calc_score(left, oper_eq, &adjust_temp)
-                        .free(&adjust_temp)
-                        .build()
-                )
+            // Undefined
+            (Type::Int, Type::Float(_)) if matches!(self, Arithmetic::Sur) => Err(undefined_operation_occured),
+            // (([Left] * MAG) [+-*/] [Right * MAG]) / MAG = ([Left] [+-*/] [Right]).floor()
+            (Type::Int, Type::Float(q)) => {
+                let adjust_temp = get_type_adjusted_temp(right.datatype);
+                let mut commands = f_constract
+                    .assign_score(&adjust_temp, left)
+                    .fltify(&adjust_temp, q)
+                    .build();
+                commands.extend(self.calc_score(&adjust_temp, right)?);
+                commands.extend(FormulaConstructer::new()
+                    .intify(&adjust_temp, q)
+                    .assign_score(left, &adjust_temp)
+                    .free(&adjust_temp)
+                    .build()
+                );
+                Ok(commands)
             },
-            (Type::Float, Type::Float) => match self {
-                // Undefined
-                Arithmetic::Sur => Err(undefined_operation_occured),
-                // ([Left * MAG] * [Right * MAG]) / MAG = ([Left] * [Right]) * MAG
-                Arithmetic::Mul => Ok(
-                    f_constract
-                        .calc_score(left, oper_eq, right)
-                        .calc_num(left, "/=".to_string(), FLOAT_MAGNIFICATION)
-                        .build()
-                ),
-                // ([Left * MAG] * MAG) / [Right * MAG] = ([Left] / [Right]) * MAG
-                Arithmetic::Div => Ok(
-                    f_constract
-                        .calc_num(left, "*=".to_string(), FLOAT_MAGNIFICATION)
-                        .calc_score(left, oper_eq, right)
-                        .build()
-                ),
-                // [Left * MAG] [+-] [Right * MAG] = ([Left] [+-] [Right]) * MAG
-                Arithmetic::Add | Arithmetic::Rem => Ok(
-                    f_constract
-                        .calc_score(left, oper_eq, right)
-                        .build()
-                )
+            // The right is an int if its decimal places are 0
+            (Type::Float(p), Type::Int | Type::Float(_)) => {
+                let q = right.datatype.places();
+                match self {
+                    // Undefined
+                    Arithmetic::Sur => Err(undefined_operation_occured),
+                    // ([Left * MAG] * [Right * MAG']) / MAG' = ([Left] * [Right]) * MAG
+                    Arithmetic::Mul => Ok(
+                        f_constract
+                            .calc_score(left, oper_eq, right)
+                            .intify(left, q)
+                            .build()
+                    ),
+                    // ([Left * MAG] * MAG') / [Right * MAG'] = ([Left] / [Right]) * MAG
+                    Arithmetic::Div => Ok(
+                        f_constract
+                            .fltify(left, q)
+                            .calc_score(left, oper_eq, right)
+                            .build()
+                    ),
+                    // [Left * MAG] [+-] [Right * MAG] = ([Left] [+-] [Right]) * MAG
+                    Arithmetic::Add | Arithmetic::Rem if p == q => Ok(
+                        f_constract
+                            .calc_score(left, oper_eq, right)
+                            .build()
+                    ),
+                    // The right is rescaled to the decimal places of the left
+                    Arithmetic::Add | Arithmetic::Rem => {
+                        let adjust_temp = get_type_adjusted_temp(left.datatype);
+                        Ok(
+                            f_constract
+                                .assign_score(&adjust_temp, right)
+                                .rescale(&adjust_temp, q, p)
+                                .calc_score(left, oper_eq, &adjust_temp)
+                                .free(&adjust_temp)
+                                .build()
+                        )
+                    }
+                }
             },
             // Undefined
             _ => Err(undefined_operation_occured)
@@ -118,14 +180,18 @@ impl Arithmetic {
     fn calc_int(&self, left:&Scoreboard, right:i32) -> Result<Vec<CommandAST>, CompileError> {
         let mut f_constract = FormulaConstructer::new();
         let oper_eq = format!("{}=", self.to_str());
-        let undefined_operation_occured = CompileError::UndefinedOperation(
-            left.datatype.clone(),
+        let undefined_operation_occured = CompileErrorKind::UndefinedOperation(
+            left.datatype,
             Oper::Arithmetic(self.clone()),
             Type::Int
-        );
+        ).at(Span::default());
+        // Only an addition and a subtraction need the right scaled,
+        // since [Left * MAG] [*/] [Right] = ([Left] [*/] [Right]) * MAG
         let scaled_right = match left.datatype {
             Type::Int => right,
-            Type::Float => right * FLOAT_MAGNIFICATION,
+            Type::Float(_) if matches!(self, Arithmetic::Sur) => return Err(undefined_operation_occured),
+            Type::Float(p) if matches!(self, Arithmetic::Add | Arithmetic::Rem) => scale_int(right, p)?,
+            Type::Float(_) => right,
             _ => {return Err(undefined_operation_occured);}
         };
         match self {
@@ -146,90 +212,60 @@ impl Arithmetic {
             )
         }
     }
-    fn calc_float(&self, left:&Scoreboard, right:f32) -> Result<Vec<CommandAST>, CompileError> {
+    fn calc_float(&self, left:&Scoreboard, right:f64) -> Result<Vec<CommandAST>, CompileError> {
         let mut f_constract = FormulaConstructer::new();
         let oper_eq = format!("{}=", self.to_str());
-        let undefined_operation_occured = CompileError::UndefinedOperation(
-            left.datatype.clone(),
+        let undefined_operation_occured = CompileErrorKind::UndefinedOperation(
+            left.datatype,
             Oper::Arithmetic(self.clone()),
-            Type::Int
-        );
-        match self {
-            Arithmetic::Add | Arithmetic::Rem => {
-                let add_rem = match self {
-                    Arithmetic::Add => "add",
-                    Arithmetic::Rem => "remove",
-                    _ => unreachable!()
-                }.to_string();
-                match left.datatype {
-                    // [Left * MAG] [+-*/%] ([Right] * [MAG]).floor()
-                    Type::Float => Ok(
-                        f_constract
-                            .add_rem_num(left, add_rem, (right * FLOAT_MAGNIFICATION as f32).floor() as i32)
-                            .build()
-                    ),
-                    // [Left] [+-*/%] [Right].floor()
-                    Type::Int => Ok(
-                        f_constract
-                            .add_rem_num(left, add_rem, right.floor() as i32)
-                            .build()
-                    ),
-                    // Undefined
-                    _ => Err(undefined_operation_occured)
-                }
-            },
-            Arithmetic::Sur => Err(undefined_operation_occured),
-            Arithmetic::Mul => match left.datatype {
-                // Left: int, Right: float
-                // ([Left] * MAG) * ([Right] * MAG).floor() / MAG^2 = ([Left] * [Right]).floor()
-                Type::Int => Ok({
-                    let type_adjusted = get_type_adjusted_temp(Type::Float);
-                    f_constract
-                        .assign_score(&type_adjusted, left)
-                        .fltify(&type_adjusted)
-                        .calc_num(&type_adjusted, oper_eq, (right * FLOAT_MAGNIFICATION as f32) as i32)
-                        .intify(&type_adjusted)
-                        .intify(&type_adjusted)
-                        .assign_score(left, &type_adjusted)
-                        .free(&type_adjusted)
-                        .build()
-                }),
-                // Left: float, Right: float
-                // [Left * MAG] * ([Right] * MAG).floor() / MAG = ([Left] * [Right]) * MAG
-                Type::Float => Ok(
-                    f_constract
-                        .calc_num(left, oper_eq, (right * FLOAT_MAGNIFICATION as f32) as i32)
-                        .intify(left)
-                        .build()
-                ),
-                // Undefined
-                _ => Err(undefined_operation_occured)
-            },
-            Arithmetic::Div => match left.datatype {
-                // Left: int, Right: float
-                // ([Left] * MAG) * [Right * MAG] / MAG = ([Left] * [Right]).floor()
-                Type::Int => Ok({
-                    let type_adjusted = get_type_adjusted_temp(Type::Float);
-                    f_constract
-                        .assign_score(&type_adjusted, left)
-                        .fltify(&type_adjusted)
-                        .fltify(&type_adjusted)
-                        .calc_num(&type_adjusted, oper_eq, (right * FLOAT_MAGNIFICATION as f32) as i32)
-                        .intify(&type_adjusted)
-                        .assign_score(left, &type_adjusted)
-                        .free(&type_adjusted)
-                        .build()
-                }),
-                // Left: float, Right: float
-                // [Left * MAG] * [Right * MAG] / MAG = ([Left] * [Right]) * MAG
-                Type::Float => Ok(
-                    f_constract
-                        .calc_num(left, oper_eq, (right * FLOAT_MAGNIFICATION as f32) as i32)
-                        .intify(left)
-                        .build()
-                ),
-                _ => Err(undefined_operation_occured)
+            Type::FLOAT
+        ).at(Span::default());
+        match (left.datatype, self) {
+            // Undefined
+            (_, Arithmetic::Sur) => Err(undefined_operation_occured),
+            // (([Left] * MAG) [+-*/] [Right * MAG]) / MAG = ([Left] [+-*/] [Right]).floor()
+            (Type::Int, _) => {
+                let adjust_temp = get_type_adjusted_temp(Type::FLOAT);
+                let mut commands = f_constract
+                    .assign_score(&adjust_temp, left)
+                    .fltify(&adjust_temp, FLOAT_PLACES)
+                    .build();
+                commands.extend(self.calc_float(&adjust_temp, right)?);
+                commands.extend(FormulaConstructer::new()
+                    .intify(&adjust_temp, FLOAT_PLACES)
+                    .assign_score(left, &adjust_temp)
+                    .free(&adjust_temp)
+                    .build()
+                );
+                Ok(commands)
             },
+            // [Left * MAG] [+-] [Right * MAG] = ([Left] [+-] [Right]) * MAG
+            (Type::Float(p), Arithmetic::Add) => Ok(
+                f_constract
+                    .add_rem_num(left, "add".to_string(), fixed_point(right, p)?)
+                    .build()
+            ),
+            (Type::Float(p), Arithmetic::Rem) => Ok(
+                f_constract
+                    .add_rem_num(left, "remove".to_string(), fixed_point(right, p)?)
+                    .build()
+            ),
+            // [Left * MAG] * [Right * MAG] / MAG = ([Left] * [Right]) * MAG
+            (Type::Float(p), Arithmetic::Mul) => Ok(
+                f_constract
+                    .calc_num(left, oper_eq, fixed_point(right, p)?)
+                    .intify(left, p)
+                    .build()
+            ),
+            // [Left * MAG] * MAG / [Right * MAG] = ([Left] / [Right]) * MAG
+            (Type::Float(p), Arithmetic::Div) => Ok(
+                f_constract
+                    .fltify(left, p)
+                    .calc_num(left, oper_eq, fixed_point(right, p)?)
+                    .build()
+            ),
+            // Undefined
+            _ => Err(undefined_operation_occured)
         }
     }
-}
\ No newline at end of file
+}
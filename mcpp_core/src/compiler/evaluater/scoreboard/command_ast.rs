@@ -1,9 +1,9 @@
-use crate::evaluater::{Type, scoreboard::get_calc_temp};
+use crate::evaluater::Type;
 
 use super::{comparison_operation::{self, Comparison}, Scoreboard};
 use crate::compiler::evaluater::Operator;
 
-use super::{NAMESPACE, FLOAT_MAGNIFICATION};
+use super::{magnification, NAMESPACE, STORAGE};
 
 #[derive(Debug, Clone)]
 pub enum CommandAST {
@@ -11,73 +11,109 @@ pub enum CommandAST {
     AddRemNum(AddRemNum),
     AssignScore(AssignScore),
     AssignNum(AssignNum),
-    BoolifyCondition(ExecuteConstructer),
+    BoolifyCondition(BoolifyCondition),
     Free(Free),
+    StoreResult(StoreResult),
+    StorageSet(StorageSet),
+    StorageRemove(StorageRemove),
+    StorageChanged(StorageChanged),
+    StorageStoreScore(StorageStoreScore),
+    CallWith(CallWith),
     Native(String),
 }
-impl Serialise for CommandAST {
-    fn serialise(&self) -> String {
+impl CommandAST {
+    /// Serialises the command, whose data storage is `<namespace>:vars`
+    pub fn serialise_in(&self, namespace:&str) -> String {
         match self {
+            CommandAST::StorageSet(s) => s.serialise_in(namespace),
+            CommandAST::StorageRemove(s) => s.serialise_in(namespace),
+            CommandAST::StorageChanged(s) => s.serialise_in(namespace),
+            CommandAST::StorageStoreScore(s) => s.serialise_in(namespace),
+            CommandAST::CallWith(c) => c.serialise_in(namespace),
             CommandAST::AddRemNum(a) => a.serialise(),
             CommandAST::AssignNum(a) => a.serialise(),
             CommandAST::AssignScore(a) => a.serialise(),
             CommandAST::CalcScore(c) => c.serialise(),
-            CommandAST::BoolifyCondition(b) => b.clone().build(),
+            CommandAST::BoolifyCondition(b) => b.serialise(),
             CommandAST::Free(f) => f.serialise(),
+            CommandAST::StoreResult(s) => s.serialise(),
             CommandAST::Native(s) => s.clone()
         }
     }
+    /// The constants the command reads, which have to be set beforehand
+    pub fn referred_constants(&self) -> Vec<i32> {
+        let targets = match self {
+            CommandAST::CalcScore(c) => vec![&c.right],
+            CommandAST::AssignScore(a) => vec![&a.right],
+            CommandAST::BoolifyCondition(b) => b.execute.conditions.iter().filter_map(|c| match c {
+                ConditionAST::Comparison(c) => Some(&c.right),
+                ConditionAST::Matches(_) => None
+            }).collect(),
+            _ => Vec::new()
+        };
+        targets.into_iter().filter_map(|t| t.constant()).collect()
+    }
 }
 
 pub struct FormulaConstructer {
-    commands: Vec<CommandAST>,
-    temp_scores: Vec<Scoreboard>
+    commands: Vec<CommandAST>
 }
 
+const CONSTANT_SCOPE:&str = "CONSTANT";
+
+/// Constants are set once on load, and they are never freed
 fn get_const(constant:i32) -> Scoreboard {
     Scoreboard {
         name: constant.to_string(),
-        scope: vec!["CONSTANT".to_string()],
+        scope: vec![CONSTANT_SCOPE.to_string()],
         datatype: Type::Int
     }
 }
 
+impl Default for FormulaConstructer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl FormulaConstructer {
     pub fn new() -> Self {
         FormulaConstructer {
-            commands: Vec::new(),
-            temp_scores: Vec::new()
+            commands: Vec::new()
         }
     }
     pub fn calc_score(&mut self, left:&Scoreboard, operator:String, right:&Scoreboard) -> &mut Self {
         self.commands.push(CommandAST::CalcScore(
             CalcScore {
                 left: ScoreTarget::from(left),
-                operator: operator,
+                operator,
                 right: ScoreTarget::from(right)
             }
         ));
-        return self
+        self
     }
     pub fn calc_num(&mut self, left:&Scoreboard, operator:String, right:i32) -> &mut Self {
-        let constant = get_const(right);
-        self.commands.push(CommandAST::AssignNum(
-            AssignNum { left: ScoreTarget::from(&constant), right: right }
-        ));
-        self.temp_scores.push(constant);
-        let constant = self.temp_scores.last().unwrap();
         self.commands.push(CommandAST::CalcScore(
             CalcScore {
                 left: ScoreTarget::from(left),
-                operator: operator,
-                right: ScoreTarget::from(constant)
+                operator,
+                right: ScoreTarget::from(&get_const(right))
             }
         ));
-        return self
+        self
+    }
+    pub fn set_constant(&mut self, constant:i32) -> &mut Self {
+        self.assign_num(&get_const(constant), constant)
     }
     pub fn add_rem_num(&mut self, left:&Scoreboard, add_rem:String, right:i32) -> &mut Self {
+        // Minecraft doesn't accept a negative amount, so the opposite one is used instead
+        let (add_rem, right) = match (add_rem.as_str(), right < 0) {
+            ("add", true) => ("remove".to_string(), right.wrapping_neg()),
+            ("remove", true) => ("add".to_string(), right.wrapping_neg()),
+            _ => (add_rem, right)
+        };
         self.commands.push(CommandAST::AddRemNum(
-            AddRemNum { left: ScoreTarget::from(left), add_rem: add_rem, right: right }
+            AddRemNum { left: ScoreTarget::from(left), add_rem, right }
         ));
         self
     }
@@ -89,63 +125,119 @@ impl FormulaConstructer {
     }
     pub fn assign_num(&mut self, left:&Scoreboard, right:i32) -> &mut Self {
         self.commands.push(CommandAST::AssignNum(
-            AssignNum { left: ScoreTarget::from(left), right: right }
+            AssignNum { left: ScoreTarget::from(left), right }
         ));
         self
     }
-    pub fn intify(&mut self, target:&Scoreboard) -> &mut Self {
-        self.calc_num(target, "/=".to_string(), FLOAT_MAGNIFICATION)
+    pub fn intify(&mut self, target:&Scoreboard, places:u32) -> &mut Self {
+        self.rescale(target, places, 0)
+    }
+    pub fn fltify(&mut self, target:&Scoreboard, places:u32) -> &mut Self {
+        self.rescale(target, 0, places)
     }
-    pub fn fltify(&mut self, target:&Scoreboard) -> &mut Self {
-        self.calc_num(target, "*=".to_string(), FLOAT_MAGNIFICATION)
+    /// Changes the decimal places of the fixed-point score, where the dropped digits are floored
+    pub fn rescale(&mut self, target:&Scoreboard, from:u32, to:u32) -> &mut Self {
+        match from.cmp(&to) {
+            std::cmp::Ordering::Less => self.calc_num(target, "*=".to_string(), magnification(to - from)),
+            std::cmp::Ordering::Greater => self.calc_num(target, "/=".to_string(), magnification(from - to)),
+            std::cmp::Ordering::Equal => self
+        }
     }
     pub fn boolify_score_comparison(&mut self, left:&Scoreboard, comparison:String, right:&Scoreboard) -> &mut Self {
-        self.commands.push(CommandAST::BoolifyCondition(
-            ExecuteConstructer {
+        self.commands.push(CommandAST::BoolifyCondition(BoolifyCondition {
+            contain_to: ScoreTarget::from(left),
+            execute: ExecuteConstructer {
                 conditions: vec![ConditionAST::Comparison(ComparisonAST {
                     is_unless: false,
                     left: ScoreTarget::from(left),
-                    comparison: comparison,
+                    comparison,
                     right: ScoreTarget::from(right)
                 })]
             }
-        ));
+        }));
         self
     }
     pub fn boolify_num_comparison(&mut self, left:&Scoreboard, comparison:String, right:i32) -> &mut Self {
-        let constant = get_const(right);
-        let const_target = ScoreTarget::from(&constant);
-        self.commands.push(CommandAST::AssignNum(
-            AssignNum { left: const_target.clone(), right: right }
-        ));
-        self.commands.push(CommandAST::BoolifyCondition(
-            ExecuteConstructer {
+        let const_target = ScoreTarget::from(&get_const(right));
+        self.commands.push(CommandAST::BoolifyCondition(BoolifyCondition {
+            contain_to: ScoreTarget::from(left),
+            execute: ExecuteConstructer {
                 conditions: vec![ConditionAST::Comparison(ComparisonAST {
                     is_unless: false,
                     left: ScoreTarget::from(left),
-                    comparison: comparison,
+                    comparison,
                     right: const_target
                 })]
             }
-        ));
-        self.temp_scores.push(constant);
+        }));
         self
     }
     pub fn validate_bool(&mut self, target:&Scoreboard) -> &mut Self {
-        let constant_0 = get_const(0);
-        self.commands.push(CommandAST::AssignNum(
-            AssignNum { left: ScoreTarget::from(&constant_0), right: 0 }
-        ));
-        self.temp_scores.push(constant_0);
-        self.commands.push(CommandAST::BoolifyCondition(
-            ExecuteConstructer {
+        self.commands.push(CommandAST::BoolifyCondition(BoolifyCondition {
+            contain_to: ScoreTarget::from(target),
+            execute: ExecuteConstructer {
                 conditions: vec![ConditionAST::Comparison(ComparisonAST {
                     is_unless: true,
                     left: ScoreTarget::from(target),
                     comparison: "==".to_string(),
-                    right: ScoreTarget::from(self.temp_scores.last().unwrap())
+                    right: ScoreTarget::from(&get_const(0))
                 })]
             }
+        }));
+        self
+    }
+    pub fn store_result(&mut self, target:&Scoreboard, command:String) -> &mut Self {
+        self.commands.push(CommandAST::StoreResult(
+            StoreResult { contain_to: ScoreTarget::from(target), command }
+        ));
+        self
+    }
+    pub fn set_string(&mut self, target:&Scoreboard, value:&str) -> &mut Self {
+        self.commands.push(CommandAST::StorageSet(StorageSet {
+            target: StorageTarget::from(target),
+            source: StorageSource::value_of(value)
+        }));
+        self
+    }
+    pub fn copy_string(&mut self, target:&Scoreboard, source:&Scoreboard) -> &mut Self {
+        self.commands.push(CommandAST::StorageSet(StorageSet {
+            target: StorageTarget::from(target),
+            source: StorageSource::From(StorageTarget::from(source))
+        }));
+        self
+    }
+    /// Overwrites the target with the source, storing into the container whether the target has changed
+    pub fn store_string_changed(&mut self, contain_to:&Scoreboard, target:&Scoreboard, source:StorageSource) -> &mut Self {
+        self.commands.push(CommandAST::StorageChanged(StorageChanged {
+            contain_to: ScoreTarget::from(contain_to),
+            set: StorageSet { target: StorageTarget::from(target), source }
+        }));
+        self
+    }
+    /// Copies the score into the storage, where a float is a decimal instead of a fixed-point number
+    pub fn store_score(&mut self, target:&Scoreboard, source:&Scoreboard) -> &mut Self {
+        let (kind, scale) = match source.datatype {
+            Type::Float(p) => ("double", 1.0 / magnification(p) as f64),
+            _ => ("int", 1.0)
+        };
+        self.commands.push(CommandAST::StorageStoreScore(StorageStoreScore {
+            target: StorageTarget::from(target),
+            kind: kind.to_string(),
+            scale,
+            source: ScoreTarget::from(source)
+        }));
+        self
+    }
+    pub fn remove_string(&mut self, target:&Scoreboard) -> &mut Self {
+        self.commands.push(CommandAST::StorageRemove(
+            StorageRemove { target: StorageTarget::from(target) }
+        ));
+        self
+    }
+    /// Calls the function, whose macro arguments are the compound of the storage
+    pub fn call_with(&mut self, function:String, arguments:&Scoreboard) -> &mut Self {
+        self.commands.push(CommandAST::CallWith(
+            CallWith { function, arguments: StorageTarget::from(arguments) }
         ));
         self
     }
@@ -156,11 +248,6 @@ impl FormulaConstructer {
         self
     }
     pub fn build(&mut self) -> Vec<CommandAST> {
-        for tmp in &self.temp_scores {
-            self.commands.push(CommandAST::Free(
-                Free { target: ScoreTarget::from(tmp) }
-            ));
-        }
         self.commands.clone()
     }
 }
@@ -171,6 +258,14 @@ pub struct ScoreTarget {
     pub objective: String,
 }
 
+impl ScoreTarget {
+    fn constant(&self) -> Option<i32> {
+        self.player
+            .strip_prefix(&format!("#{}.", CONSTANT_SCOPE))?
+            .parse()
+            .ok()
+    }
+}
 impl From<&Scoreboard> for ScoreTarget {
     fn from(sb: &Scoreboard) -> Self {
         ScoreTarget {
@@ -180,6 +275,35 @@ impl From<&Scoreboard> for ScoreTarget {
     }
 }
 
+/// A value in the data storage, whose path is the name of the scoreboard without `#`
+#[derive(Debug, Clone, PartialEq)]
+pub struct StorageTarget {
+    pub path: String
+}
+impl From<&Scoreboard> for StorageTarget {
+    fn from(sb: &Scoreboard) -> Self {
+        StorageTarget { path: sb.get_mcname().trim_start_matches('#').to_string() }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum StorageSource {
+    Value(String),
+    From(StorageTarget)
+}
+impl StorageSource {
+    /// A string literal of SNBT
+    pub fn value_of(value:&str) -> Self {
+        StorageSource::Value(format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"")))
+    }
+    fn serialise_in(&self, namespace:&str) -> String {
+        match self {
+            StorageSource::Value(v) => format!("value {}", v),
+            StorageSource::From(f) => format!("from storage {}:{} {}", namespace, STORAGE, f.path)
+        }
+    }
+}
+
 pub trait Serialise {
     fn serialise(&self) -> String;
 }
@@ -256,20 +380,14 @@ pub struct BoolifyCondition {
 }
 impl Serialise for BoolifyCondition {
     fn serialise(&self) -> String {
-        let temp = get_calc_temp(Type::Bool);
-        let temp_target = ScoreTarget::from(&temp);
-        [
-            AssignNum { left:temp_target.clone(), right: 0 }.serialise(),
-            format!(
-                "{}{}",
-                self.execute.clone().build(),
-                AssignNum { left: temp_target.clone(), right: 1 }.serialise()
-            ),
-            AssignScore {
-                left: self.contain_to.clone(), right: temp_target.clone()
-            }.serialise(),
-            Free { target: temp_target }.serialise()
-        ].join("\n")
+        // The conditions are tested before the result is stored,
+        // so the container can be one of the compared scores.
+        format!(
+            "execute store success score {} {} {}",
+            self.contain_to.player,
+            self.contain_to.objective,
+            self.execute.serialise_conditions()
+        )
     }
 }
 
@@ -283,11 +401,116 @@ impl Serialise for Free {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct StoreResult {
+    contain_to: ScoreTarget,
+    command: String
+}
+impl Serialise for StoreResult {
+    fn serialise(&self) -> String {
+        format!(
+            "execute store result score {} {} run {}",
+            self.contain_to.player,
+            self.contain_to.objective,
+            self.command
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StorageSet {
+    target: StorageTarget,
+    source: StorageSource
+}
+impl StorageSet {
+    fn serialise_in(&self, namespace:&str) -> String {
+        format!(
+            "data modify storage {}:{} {} set {}",
+            namespace,
+            STORAGE,
+            self.target.path,
+            self.source.serialise_in(namespace)
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StorageRemove {
+    target: StorageTarget
+}
+impl StorageRemove {
+    fn serialise_in(&self, namespace:&str) -> String {
+        format!("data remove storage {}:{} {}", namespace, STORAGE, self.target.path)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StorageChanged {
+    contain_to: ScoreTarget,
+    set: StorageSet
+}
+impl StorageChanged {
+    fn serialise_in(&self, namespace:&str) -> String {
+        // `data modify` fails if nothing has changed
+        format!(
+            "execute store success score {} {} run {}",
+            self.contain_to.player,
+            self.contain_to.objective,
+            self.set.serialise_in(namespace)
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StorageStoreScore {
+    target: StorageTarget,
+    kind: String,
+    scale: f64,
+    source: ScoreTarget
+}
+impl StorageStoreScore {
+    fn serialise_in(&self, namespace:&str) -> String {
+        format!(
+            "execute store result storage {}:{} {} {} {} run scoreboard players get {} {}",
+            namespace,
+            STORAGE,
+            self.target.path,
+            self.kind,
+            self.scale,
+            self.source.player,
+            self.source.objective
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CallWith {
+    function: String,
+    arguments: StorageTarget
+}
+impl CallWith {
+    fn serialise_in(&self, namespace:&str) -> String {
+        format!(
+            "function {}:{} with storage {}:{} {}",
+            namespace,
+            self.function,
+            namespace,
+            STORAGE,
+            self.arguments.path
+        )
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct ExecuteConstructer {
     conditions: Vec<ConditionAST>
 }
+impl Default for ExecuteConstructer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ExecuteConstructer {
     pub fn new() -> Self {
         ExecuteConstructer {
@@ -298,7 +521,7 @@ impl ExecuteConstructer {
         let is_unless = matches!(operator, &Comparison::Neq);
         let neq = Comparison::Neq.to_str();
         let ast= ComparisonAST {
-            is_unless: is_unless,
+            is_unless,
             left: ScoreTarget::from(lhs),
             right: ScoreTarget::from(rhs),
             comparison: (if is_unless {neq} else {operator.to_str()}).to_string()
@@ -306,15 +529,23 @@ impl ExecuteConstructer {
         self.conditions.push(ConditionAST::Comparison(ast));
         self
     }
+    pub fn matches(mut self, target:&Scoreboard, is_unless:bool, range:String) -> Self {
+        self.conditions.push(ConditionAST::Matches(MatchesAST {
+            is_unless,
+            target: ScoreTarget::from(target),
+            range
+        }));
+        self
+    }
+    fn serialise_conditions(&self) -> String {
+        self.conditions
+            .iter()
+            .map(|c| c.serialise())
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
     pub fn build(self) -> String {
-        format!(
-            "execute {} run ",
-            self.conditions
-                .iter()
-                .map(|c| c.serialise())
-                .collect::<Vec<String>>()
-                .join(" ")
-        )   
+        format!("execute {} run ", self.serialise_conditions())
     }
 }
 impl From<Vec<ConditionAST>> for ExecuteConstructer {
@@ -324,16 +555,36 @@ impl From<Vec<ConditionAST>> for ExecuteConstructer {
 }
 #[derive(Debug, Clone)]
 pub enum ConditionAST {
-    Comparison(ComparisonAST)
+    Comparison(ComparisonAST),
+    Matches(MatchesAST)
 }
 impl Serialise for ConditionAST {
     fn serialise(&self) -> String {
         match self {
-            ConditionAST::Comparison(c) => c.serialise()
+            ConditionAST::Comparison(c) => c.serialise(),
+            ConditionAST::Matches(m) => m.serialise()
         }
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct MatchesAST {
+    is_unless: bool,
+    target: ScoreTarget,
+    range: String
+}
+impl Serialise for MatchesAST {
+    fn serialise(&self) -> String {
+        format!(
+            "{} score {} {} matches {}",
+            if self.is_unless {"unless"} else {"if"},
+            self.target.player,
+            self.target.objective,
+            self.range
+        )
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ComparisonAST {
     is_unless: bool,
@@ -345,12 +596,12 @@ impl Serialise for ComparisonAST {
     fn serialise(&self) -> String {
         let neq = comparison_operation::Comparison::Neq.to_str();
         let eq = comparison_operation::Comparison::Eq.to_str();
+        // Minecraft spells equality as "=" and has no inequality
         if self.comparison.as_str() == neq {
             format!(
-                "unless score {} {} {} {} {}",
+                "unless score {} {} = {} {}",
                 self.left.player,
                 self.left.objective,
-                eq,
                 self.right.player,
                 self.right.objective
             )
@@ -364,7 +615,7 @@ impl Serialise for ComparisonAST {
                 },
                 self.left.player,
                 self.left.objective,
-                self.comparison,
+                if self.comparison.as_str() == eq {"="} else {self.comparison.as_str()},
                 self.right.player,
                 self.right.objective
             )
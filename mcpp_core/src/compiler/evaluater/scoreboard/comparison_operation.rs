@@ -1,7 +1,7 @@
 use super::super::Operator;
-use super::command_ast::{FormulaConstructer, CommandAST};
-use super::{get_type_adjusted_temp, Scoreboard, FLOAT_MAGNIFICATION};
-use crate::compiler::CompileError;
+use super::command_ast::{FormulaConstructer, CommandAST, StorageSource, StorageTarget};
+use super::{fixed_point, get_type_adjusted_temp, scale_int, Scoreboard, FLOAT_PLACES};
+use crate::compiler::{CompileError, CompileErrorKind, Span};
 use crate::evaluater::{Oper, Type};
 use crate::compiler::ast::serialiser::IToken;
 
@@ -10,7 +10,7 @@ pub enum Comparison { Gt, Ge, Lt, Le, Eq, Neq }
 
 impl Operator for Comparison {
     fn get_priority(&self) -> u32 {
-        0
+        3
     }
     fn to_str(&self) -> &str {
         match self {
@@ -23,57 +23,89 @@ impl Operator for Comparison {
         }
     }
     fn calc(&self, left:&Scoreboard, right:&IToken) -> Result<Vec<CommandAST>, CompileError> {
+        if left.datatype == Type::Str {
+            return self.compare_string(left, right);
+        }
         match right {
             IToken::Scr(s) => self.compare_score(left, s),
             IToken::Int(i) => self.compare_int(left, *i),
             IToken::Flt(f) => self.compare_float(left, *f),
             IToken::Bln(b) => self.compare_bool(left, *b),
-            _ => Err(CompileError::TheTokenIsntValue(right.clone()))
+            _ => Err(CompileErrorKind::TheTokenIsntValue(Box::new(right.clone())).at(Span::default()))
         }
     }
     fn get_type(&self, left:&Type, right:&Type) -> Option<Type> {
+        // Strings can only be tested if they're the same
+        if (left == &Type::Str || right == &Type::Str) && !matches!(self, Self::Eq | Self::Neq) {
+            return None
+        }
         if left == right {
             return Some(Type::Bool)
         }
         match (left, right) {
-            (Type::Int, Type::Float) => Some(Type::Bool),
-            (Type::Float, Type::Int) => Some(Type::Bool),
+            (l, r) if l.is_number() && r.is_number() => Some(Type::Bool),
             _ => None
         }
     }
 }
 impl Comparison {
+    /// Overwrites the left string with the right one, which fails if they're the same
+    fn compare_string(&self, left:&Scoreboard, right:&IToken) -> Result<Vec<CommandAST>, CompileError> {
+        let source = match right {
+            IToken::Str(s) => StorageSource::value_of(s),
+            IToken::Scr(s) if s.datatype == Type::Str => StorageSource::From(StorageTarget::from(s)),
+            _ => return Err(CompileErrorKind::TheTokenIsntValue(Box::new(right.clone())).at(Span::default()))
+        };
+        let mut f_constract = FormulaConstructer::new();
+        f_constract.store_string_changed(left, left, source);
+        match self {
+            Self::Eq => Ok(f_constract.boolify_num_comparison(left, Self::Eq.to_str().to_string(), 0).build()),
+            Self::Neq => Ok(f_constract.build()),
+            _ => Err(CompileErrorKind::UndefinedOperation(Type::Str, Oper::Comparison(self.clone()), Type::Str).at(Span::default()))
+        }
+    }
     fn compare_score(&self, left:&Scoreboard, right:&Scoreboard) -> Result<Vec<CommandAST>, CompileError> {
         let mut f_constract = FormulaConstructer::new();
-        let undefined_operation_occured = CompileError::UndefinedOperation(
-            left.datatype.clone(),
+        let undefined_operation_occured = CompileErrorKind::UndefinedOperation(
+            left.datatype,
             Oper::Comparison(self.clone()),
-            right.datatype.clone()
-        );
+            right.datatype
+        ).at(Span::default());
         let cmp = self.to_str().to_string();
+        let (p, q) = (left.datatype.places(), right.datatype.places());
         match (left.datatype, right.datatype) {
-            (Type::Int, Type::Int) | (Type::Float, Type::Float) | (Type::Bool, Type::Bool) => Ok(
+            (Type::Bool, Type::Bool) => Ok(
+                f_constract
+                    .boolify_score_comparison(
+                        left, cmp, right
+                    )
+                    .build()
+            ),
+            (l, r) if l.is_number() && r.is_number() && p == q => Ok(
                 f_constract
                     .boolify_score_comparison(
                         left, cmp, right
                     )
                     .build()
             ),
-            (Type::Float, Type::Int) => Ok({
-                let adjusted = get_type_adjusted_temp(Type::Float);
+            // They're compared in the more decimal places not to lose any digit
+            (l, r) if l.is_number() && r.is_number() && p > q => Ok({
+                let adjusted = get_type_adjusted_temp(left.datatype);
                 f_constract
                     .assign_score(&adjusted, right)
-                    .fltify(&adjusted)
+                    .rescale(&adjusted, q, p)
                     .boolify_score_comparison(left,cmp, &adjusted)
                     .free(&adjusted)
                     .build()
             }),
-            (Type::Int, Type::Float) => Ok({
-                let adjusted = get_type_adjusted_temp(Type::Float);
+            // The result is stored into the adjusted one, then moved to the left
+            (l, r) if l.is_number() && r.is_number() => Ok({
+                let adjusted = get_type_adjusted_temp(right.datatype);
                 f_constract
                     .assign_score(&adjusted, left)
-                    .fltify(&adjusted)
+                    .rescale(&adjusted, p, q)
                     .boolify_score_comparison(&adjusted,cmp, right)
+                    .assign_score(left, &adjusted)
                     .free(&adjusted)
                     .build()
             }),
@@ -82,11 +114,11 @@ impl Comparison {
     }
     fn compare_int(&self, left:&Scoreboard, right:i32) -> Result<Vec<CommandAST>, CompileError> {
         let mut f_constract = FormulaConstructer::new();
-        let undefined_operation_occured = CompileError::UndefinedOperation(
-            left.datatype.clone(),
+        let undefined_operation_occured = CompileErrorKind::UndefinedOperation(
+            left.datatype,
             Oper::Comparison(self.clone()),
             Type::Int
-        );
+        ).at(Span::default());
         let cmp = self.to_str().to_string();
         match left.datatype {
             Type::Int => Ok(
@@ -94,38 +126,38 @@ impl Comparison {
                     .boolify_num_comparison(left, cmp, right)
                     .build()
             ),
-            Type::Float => Ok(
+            Type::Float(p) => Ok(
                 f_constract
-                    .boolify_num_comparison(left, cmp, right * FLOAT_MAGNIFICATION)
+                    .boolify_num_comparison(left, cmp, scale_int(right, p)?)
                     .build()
             ),
             _ => Err(undefined_operation_occured)
         }
     }
-    fn compare_float(&self, left:&Scoreboard, right:f32) -> Result<Vec<CommandAST>, CompileError> {
+    fn compare_float(&self, left:&Scoreboard, right:f64) -> Result<Vec<CommandAST>, CompileError> {
         let mut f_constract = FormulaConstructer::new();
-        let undefined_operation_occured = CompileError::UndefinedOperation(
-            left.datatype.clone(),
+        let undefined_operation_occured = CompileErrorKind::UndefinedOperation(
+            left.datatype,
             Oper::Comparison(self.clone()),
-            Type::Int
-        );
-        let scaled = (right * FLOAT_MAGNIFICATION as f32).floor() as i32;
+            Type::FLOAT
+        ).at(Span::default());
         let cmp = self.to_str().to_string();
         match left.datatype {
             Type::Int => {
-                let adjusted = get_type_adjusted_temp(Type::Float);
+                let adjusted = get_type_adjusted_temp(Type::FLOAT);
                 Ok(
                     f_constract
                         .assign_score(&adjusted, left)
-                        .fltify(&adjusted)
-                        .boolify_num_comparison(left, cmp, scaled)
+                        .fltify(&adjusted, FLOAT_PLACES)
+                        .boolify_num_comparison(&adjusted, cmp, fixed_point(right, FLOAT_PLACES)?)
+                        .assign_score(left, &adjusted)
                         .free(&adjusted)
                         .build()
                 )
             },
-            Type::Float => Ok(
+            Type::Float(p) => Ok(
                 f_constract
-                    .boolify_num_comparison(left, cmp, scaled)
+                    .boolify_num_comparison(left, cmp, fixed_point(right, p)?)
                     .build()
             ),
             _ => Err(undefined_operation_occured)
@@ -133,11 +165,11 @@ impl Comparison {
     }
     fn compare_bool(&self, left:&Scoreboard, right:bool) -> Result<Vec<CommandAST>, CompileError> {
         let mut f_constract = FormulaConstructer::new();
-        let undefined_operation_occured = CompileError::UndefinedOperation(
-            left.datatype.clone(),
+        let undefined_operation_occured = CompileErrorKind::UndefinedOperation(
+            left.datatype,
             Oper::Comparison(self.clone()),
-            Type::Int
-        );
+            Type::Bool
+        ).at(Span::default());
         match left.datatype {
             Type::Bool => match self {
                 Self::Eq => Ok(
@@ -1,25 +1,24 @@
 use super::super::Operator;
 use super::command_ast::{FormulaConstructer, CommandAST};
 use super::Scoreboard;
-use crate::compiler::CompileError;
+use crate::compiler::{CompileError, CompileErrorKind, Span};
 use crate::evaluater::{Oper, Type};
 use crate::compiler::ast::serialiser::IToken;
 
 #[derive(Debug, Clone)]
-pub enum Logical { And, Or, Not }
+pub enum Logical { And, Or }
 
 impl Operator for Logical {
     fn get_priority(&self) -> u32 {
         match self {
-            Self::And | Self::Or => 1,
-            Self::Not => 0
+            Self::And => 2,
+            Self::Or => 1
         }
     }
     fn to_str(&self) -> &str {
         match self {
             Self::And => "&",
-            Self::Or  => "|",
-            Self::Not => "!"
+            Self::Or  => "|"
         }
     }
     fn calc(&self, left:&Scoreboard, right:&IToken) -> Result<Vec<CommandAST>, CompileError> {
@@ -27,12 +26,12 @@ impl Operator for Logical {
             IToken::Scr(s) => self.logicalc_score(left, s),
             IToken::Bln(b) => self.logicalc_bool(left, *b),
             IToken::Int(_) => Err(
-                CompileError::UndefinedOperation(left.datatype, Oper::Logical(self.clone()), Type::Int)
+                CompileErrorKind::UndefinedOperation(left.datatype, Oper::Logical(self.clone()), Type::Int).at(Span::default())
             ),
             IToken::Flt(_) => Err(
-                CompileError::UndefinedOperation(left.datatype, Oper::Logical(self.clone()), Type::Float)
+                CompileErrorKind::UndefinedOperation(left.datatype, Oper::Logical(self.clone()), Type::FLOAT).at(Span::default())
             ),
-            _ => Err(CompileError::TheTokenIsntValue(right.clone()))
+            _ => Err(CompileErrorKind::TheTokenIsntValue(Box::new(right.clone())).at(Span::default()))
         }
     }
     fn get_type(&self, left:&Type, right:&Type) -> Option<Type> {
@@ -44,13 +43,24 @@ impl Operator for Logical {
     }
 }
 impl Logical {
+    /// The name of a function evaluating the right operand, such as `and_0`
+    pub fn get_name(&self) -> &str {
+        match self {
+            Self::And => "and",
+            Self::Or => "or"
+        }
+    }
+    /// The right operand is evaluated only if the left one is this, since it decides the result otherwise
+    pub fn evaluates_right_on(&self) -> bool {
+        matches!(self, Self::And)
+    }
     fn logicalc_score(&self, left:&Scoreboard, right:&Scoreboard) -> Result<Vec<CommandAST>, CompileError> {
         let mut f_constract = FormulaConstructer::new();
-        let undefined_operation_occured = CompileError::UndefinedOperation(
-            left.datatype.clone(),
+        let undefined_operation_occured = CompileErrorKind::UndefinedOperation(
+            left.datatype,
             Oper::Logical(self.clone()),
-            right.datatype.clone()
-        );
+            right.datatype
+        ).at(Span::default());
         match (left.datatype, right.datatype) {
             (Type::Bool, Type::Bool) => match self {
                 Logical::And => Ok(
@@ -63,19 +73,18 @@ impl Logical {
                         .calc_score(left, "+=".to_string(), right)
                         .validate_bool(left)
                         .build()
-                ),
-                Logical::Not => todo!()
+                )
             },
             _ => Err(undefined_operation_occured)
         }
     }
     fn logicalc_bool(&self, left:&Scoreboard, right:bool) -> Result<Vec<CommandAST>, CompileError> {
         let mut f_constract = FormulaConstructer::new();
-        let undefined_operation_occured = CompileError::UndefinedOperation(
-            left.datatype.clone(),
+        let undefined_operation_occured = CompileErrorKind::UndefinedOperation(
+            left.datatype,
             Oper::Logical(self.clone()),
             Type::Bool
-        );
+        ).at(Span::default());
         match left.datatype {
             Type::Bool => match self {
                 Logical::And => match right {
@@ -93,8 +102,7 @@ impl Logical {
                             .build()
                     ),
                     false => Ok(Vec::new())
-                },
-                _ => Err(undefined_operation_occured)
+                }
             }
             _ => Err(undefined_operation_occured)
         }
@@ -0,0 +1,96 @@
+use super::command_ast::{FormulaConstructer, CommandAST};
+use super::comparison_operation::Comparison;
+use super::Scoreboard;
+use crate::compiler::{CompileError, CompileErrorKind, Span};
+use crate::evaluater::{Oper, Operator, Type};
+use crate::compiler::ast::serialiser::IToken;
+
+/// An operator of only one operand, which is placed before it except a cast, `a as int`
+#[derive(Debug, Clone)]
+pub enum Unary { Neg, Pos, Not, Cast(Type) }
+
+impl Unary {
+    /// A unary operator binds tighter than any binary one, e.g. `-a * b` is `(-a) * b`
+    pub fn get_priority(&self) -> u32 {
+        6
+    }
+    pub fn to_str(&self) -> &str {
+        match self {
+            Self::Neg => "-",
+            Self::Pos => "+",
+            Self::Not => "!",
+            Self::Cast(_) => "as"
+        }
+    }
+    pub fn is_postfix(&self) -> bool {
+        matches!(self, Self::Cast(_))
+    }
+    pub fn get_type(&self, operand:&Type) -> Option<Type> {
+        match (self, operand) {
+            (Self::Neg | Self::Pos, Type::Int | Type::Float(_)) => Some(*operand),
+            (Self::Not, Type::Bool) => Some(Type::Bool),
+            // Numbers and bools can be converted to each other
+            (Self::Cast(to), from) if (from.is_number() || from == &Type::Bool)
+                && (to.is_number() || to == &Type::Bool) => Some(*to),
+            _ => None
+        }
+    }
+    /// Calculates the operation without any command if it's possible,
+    /// that is, on a literal or with a positive sign
+    pub fn fold(&self, operand:&IToken) -> Option<IToken> {
+        match (self, operand) {
+            (Self::Neg, IToken::Int(i)) => Some(IToken::Int(i.wrapping_neg())),
+            (Self::Neg, IToken::Flt(f)) => Some(IToken::Flt(-f)),
+            (Self::Not, IToken::Bln(b)) => Some(IToken::Bln(!b)),
+            (Self::Pos, _) => Some(operand.clone()),
+            // A float literal keeps its digits until it's assigned at the decimal places
+            (Self::Cast(Type::Int), IToken::Int(i)) => Some(IToken::Int(*i)),
+            (Self::Cast(Type::Int), IToken::Flt(f)) => Some(IToken::Int(f.floor() as i32)),
+            (Self::Cast(Type::Int), IToken::Bln(b)) => Some(IToken::Int(*b as i32)),
+            (Self::Cast(Type::Bool), IToken::Int(i)) => Some(IToken::Bln(*i != 0)),
+            (Self::Cast(Type::Bool), IToken::Flt(f)) => Some(IToken::Bln(*f != 0.0)),
+            (Self::Cast(Type::Bool), IToken::Bln(b)) => Some(IToken::Bln(*b)),
+            _ => None
+        }
+    }
+    /// Calculates the operation on the target in place, which has the type of the operand
+    pub fn calc(&self, target:&Scoreboard) -> Result<Vec<CommandAST>, CompileError> {
+        let mut f_constract = FormulaConstructer::new();
+        match (self, target.datatype) {
+            // [Target] * -1, which is the same for a fixed-point float
+            (Self::Neg, Type::Int | Type::Float(_)) => Ok(
+                f_constract
+                    .calc_num(target, "*=".to_string(), -1)
+                    .build()
+            ),
+            (Self::Pos, Type::Int | Type::Float(_)) => Ok(Vec::new()),
+            // [Target] == false
+            (Self::Not, Type::Bool) => Ok(
+                f_constract
+                    .boolify_num_comparison(target, Comparison::Eq.to_str().to_string(), 0)
+                    .build()
+            ),
+            // The score is rescaled to the decimal places, which are 0 for an int
+            (Self::Cast(to), from) if from.is_number() && to.is_number() => Ok(
+                f_constract
+                    .rescale(target, from.places(), to.places())
+                    .build()
+            ),
+            // [Target] != 0, which is 1 if it's true
+            (Self::Cast(Type::Bool), from) if from.is_number() || from == Type::Bool => Ok(
+                f_constract
+                    .validate_bool(target)
+                    .build()
+            ),
+            // True is normalised to 1 before it's scaled
+            (Self::Cast(to), Type::Bool) if to.is_number() => Ok(
+                f_constract
+                    .validate_bool(target)
+                    .fltify(target, to.places())
+                    .build()
+            ),
+            // Undefined
+            _ => Err(CompileErrorKind::UndefinedUnaryOperation(Oper::Unary(self.clone()), target.datatype).at(Span::default()))
+        }
+    }
+}
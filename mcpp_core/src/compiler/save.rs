@@ -1,30 +1,162 @@
-use std::fs::File;
-use std::io::Write;
+use std::fs::{self, File};
+use std::io::{self, Write};
 use std::path::Path;
-use std::path::PathBuf;
-use zip::result::ZipResult;
 use zip::ZipWriter;
 use zip::write::FileOptions;
 
-use super::Compiler;
+use super::ast::serialiser::MCFunction;
 
-impl Compiler {
-    fn save(&self, path:&Path) -> ZipResult<()> {
-        let mut zw = ZipWriter::new(
-            File::create(format!("{}.zip", self.namespace))?
+/// The pack format of Minecraft 1.21
+pub const DEFAULT_PACK_FORMAT:u32 = 48;
+/// Since this pack format, the directories are called `function` instead of `functions`
+const SINGULAR_DIRECTORY_SINCE:u32 = 45;
+
+#[test]
+fn exporter_test() {
+    let compiled = crate::compile_in("demo", "fn tick() {} let a = 1;").unwrap();
+    let files = DatapackExporter::new()
+        .description("A \"demo\" pack")
+        .functions("demo", &compiled)
+        .on_load("demo:main")
+        .on_tick("demo:tick")
+        .files();
+    let paths = files.iter().map(|(p, _)| p.as_str()).collect::<Vec<&str>>();
+    assert_eq!(files[0], (
+        "pack.mcmeta".to_string(),
+        "{\n  \"pack\": {\n    \"pack_format\": 48,\n    \"description\": \"A \\\"demo\\\" pack\"\n  }\n}\n".to_string()
+    ));
+    assert!(paths.contains(&"data/demo/function/tick.mcfunction"));
+    assert!(paths.contains(&"data/minecraft/tags/function/load.json"));
+    let tick = files.iter().find(|(p, _)| p == "data/minecraft/tags/function/tick.json").unwrap();
+    assert_eq!(tick.1, "{\n  \"values\": [\n    \"demo:tick\"\n  ]\n}\n");
+
+    let old = DatapackExporter::new().pack_format(15).functions("demo", &compiled).on_load("demo:tick").files();
+    assert!(old.iter().any(|(p, _)| p == "data/demo/functions/tick.mcfunction"));
+    assert!(old.iter().any(|(p, _)| p == "data/minecraft/tags/functions/load.json"));
+}
+
+/// Exports mcfunctions as a datapack into a directory or a zip
+pub struct DatapackExporter {
+    pack_format: u32,
+    description: String,
+    functions: Vec<(String, MCFunction)>,
+    load: Vec<String>,
+    tick: Vec<String>
+}
+impl Default for DatapackExporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DatapackExporter {
+    pub fn new() -> Self {
+        DatapackExporter {
+            pack_format: DEFAULT_PACK_FORMAT,
+            description: String::from("Compiled by MC++"),
+            functions: Vec::new(),
+            load: Vec::new(),
+            tick: Vec::new()
+        }
+    }
+    pub fn pack_format(mut self, pack_format:u32) -> Self {
+        self.pack_format = pack_format;
+        self
+    }
+    pub fn description(mut self, description:&str) -> Self {
+        self.description = description.to_string();
+        self
+    }
+    /// Adds the mcfunctions into the namespace
+    pub fn functions(mut self, namespace:&str, mcfunctions:&[MCFunction]) -> Self {
+        self.functions.extend(
+            mcfunctions.iter().map(|f| (namespace.to_string(), f.clone()))
         );
+        self
+    }
+    /// Runs the function such as `namespace:main` when the datapack is loaded
+    pub fn on_load(mut self, function:&str) -> Self {
+        self.load.push(function.to_string());
+        self
+    }
+    /// Runs the function such as `namespace:tick` every tick
+    pub fn on_tick(mut self, function:&str) -> Self {
+        self.tick.push(function.to_string());
+        self
+    }
+    fn function_directory(&self) -> &'static str {
+        if self.pack_format >= SINGULAR_DIRECTORY_SINCE {"function"} else {"functions"}
+    }
+    /// The relative paths of the files in the datapack and their contents
+    pub fn files(&self) -> Vec<(String, String)> {
+        let mut files = vec![(
+            "pack.mcmeta".to_string(),
+            format!(
+                "{{\n  \"pack\": {{\n    \"pack_format\": {},\n    \"description\": \"{}\"\n  }}\n}}\n",
+                self.pack_format,
+                escape_json(&self.description)
+            )
+        )];
+        for (namespace, mcfunction) in &self.functions {
+            files.push((
+                format!("data/{}/{}/{}.mcfunction", namespace, self.function_directory(), mcfunction.get_full_path()),
+                mcfunction.inside.clone()
+            ));
+        }
+        for (tag, functions) in [("load", &self.load), ("tick", &self.tick)] {
+            if functions.is_empty() {
+                continue;
+            }
+            let values = functions
+                .iter()
+                .map(|f| format!("    \"{}\"", escape_json(f)))
+                .collect::<Vec<String>>()
+                .join(",\n");
+            files.push((
+                format!("data/minecraft/tags/{}/{}.json", self.function_directory(), tag),
+                format!("{{\n  \"values\": [\n{}\n  ]\n}}\n", values)
+            ));
+        }
+        files
+    }
+    /// Writes the datapack into the directory, which is created if it doesn't exist
+    pub fn export_dir(&self, path:&Path) -> io::Result<()> {
+        for (relative, content) in self.files() {
+            let file = path.join(relative);
+            if let Some(parent) = file.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(file, content)?;
+        }
+        Ok(())
+    }
+    /// Writes the datapack as a zip file at the path
+    pub fn export_zip(&self, path:&Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut zw = ZipWriter::new(File::create(path)?);
         let opts:FileOptions<'_, ()> = FileOptions::default();
 
-        for mcf in &self.compiled {
-            let mut path = path.join(mcf.path.iter().collect::<PathBuf>());
-            path.push(format!("{}.mcfunction", mcf.name));
-            zw.start_file(path.to_string_lossy().as_ref(), opts)?;
-            zw.write(mcf.inside.as_bytes())?;
+        // Entries in a zip are always separated by '/'
+        for (relative, content) in self.files() {
+            zw.start_file(relative, opts)?;
+            zw.write_all(content.as_bytes())?;
         }
-
-        zw.flush()?;
         zw.finish()?;
 
         Ok(())
     }
+}
+
+fn escape_json(input:&str) -> String {
+    input
+        .chars()
+        .map(|c| match c {
+            '"' => "\\\"".to_string(),
+            '\\' => "\\\\".to_string(),
+            '\n' => "\\n".to_string(),
+            c => c.to_string()
+        })
+        .collect()
 }
\ No newline at end of file
@@ -0,0 +1,142 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::{CompileError, CompileErrorKind, Span};
+
+/// The pack formats of the versions, each of which is used until the next one
+const PACK_FORMATS:[(TargetVersion, u32); 15] = [
+    (TargetVersion::new(15, 0), 5),
+    (TargetVersion::new(16, 2), 6),
+    (TargetVersion::new(17, 0), 7),
+    (TargetVersion::new(18, 0), 8),
+    (TargetVersion::new(18, 2), 9),
+    (TargetVersion::new(19, 0), 10),
+    (TargetVersion::new(19, 4), 12),
+    (TargetVersion::new(20, 0), 15),
+    (TargetVersion::new(20, 2), 18),
+    (TargetVersion::new(20, 3), 26),
+    (TargetVersion::new(20, 5), 41),
+    (TargetVersion::new(21, 0), 48),
+    (TargetVersion::new(21, 2), 57),
+    (TargetVersion::new(21, 4), 61),
+    (TargetVersion::new(21, 5), 71)
+];
+
+/// A command which some versions of Minecraft don't have
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Feature {
+    /// `$` lines of a function filled by `function ... with`
+    FunctionMacros,
+    /// `return` and `execute if function`
+    ReturnCommand
+}
+impl Feature {
+    /// The first version which has it
+    pub fn since(&self) -> TargetVersion {
+        match self {
+            Feature::FunctionMacros => TargetVersion::new(20, 2),
+            Feature::ReturnCommand => TargetVersion::new(20, 3)
+        }
+    }
+}
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Feature::FunctionMacros => write!(f, "function macros"),
+            Feature::ReturnCommand => write!(f, "the `return` command")
+        }
+    }
+}
+
+/// The version of Minecraft `1.minor.patch` the datapack is compiled for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TargetVersion {
+    pub minor: u32,
+    pub patch: u32
+}
+impl TargetVersion {
+    /// The oldest version supported, which is the first one with the data storage
+    pub const OLDEST:TargetVersion = TargetVersion::new(15, 0);
+    /// The newest version whose pack format is known, whose one is used for the later versions
+    pub const NEWEST:TargetVersion = PACK_FORMATS[PACK_FORMATS.len() - 1].0;
+    pub const fn new(minor:u32, patch:u32) -> Self {
+        TargetVersion { minor, patch }
+    }
+    pub fn pack_format(&self) -> u32 {
+        PACK_FORMATS
+            .iter()
+            .rev()
+            .find(|(version, _)| version <= self)
+            .map_or(PACK_FORMATS[0].1, |(_, pack_format)| *pack_format)
+    }
+    /// Whether its pack format is known, not guessed from the newest one
+    pub fn is_known(&self) -> bool {
+        *self <= TargetVersion::NEWEST
+    }
+    pub fn supports(&self, feature:Feature) -> bool {
+        *self >= feature.since()
+    }
+    /// Fails unless the version has the feature, which is needed for the construct
+    pub fn require(&self, feature:Feature, construct:&str, span:Span) -> Result<(), CompileError> {
+        if self.supports(feature) {
+            Ok(())
+        } else {
+            Err(CompileErrorKind::UnsupportedByTarget(construct.to_string(), feature, *self).at(span))
+        }
+    }
+}
+/// Minecraft 1.21, whose pack format is the default of the exporter
+impl Default for TargetVersion {
+    fn default() -> Self {
+        TargetVersion::new(21, 0)
+    }
+}
+impl fmt::Display for TargetVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.patch {
+            0 => write!(f, "1.{}", self.minor),
+            _ => write!(f, "1.{}.{}", self.minor, self.patch)
+        }
+    }
+}
+/// Parses a version such as `1.20.4` or `1.21`
+impl FromStr for TargetVersion {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let numbers = s
+            .split('.')
+            .map(|n| n.parse::<u32>())
+            .collect::<Result<Vec<u32>, _>>()
+            .map_err(|_| format!("`{}` is not a version of Minecraft such as 1.20.4", s))?;
+        let version = match numbers.as_slice() {
+            [1, minor] => TargetVersion::new(*minor, 0),
+            [1, minor, patch] => TargetVersion::new(*minor, *patch),
+            _ => return Err(format!("`{}` is not a version of Minecraft such as 1.20.4", s))
+        };
+        if version < TargetVersion::OLDEST {
+            return Err(format!("Minecraft {} isn't supported, which has to be {} or later", version, TargetVersion::OLDEST));
+        }
+        Ok(version)
+    }
+}
+
+#[test]
+fn version_test() {
+    let version = "1.20.4".parse::<TargetVersion>().unwrap();
+    assert_eq!(version, TargetVersion::new(20, 4));
+    assert_eq!((version.to_string(), version.pack_format()), ("1.20.4".to_string(), 26));
+    assert_eq!("1.21".parse::<TargetVersion>().unwrap().to_string(), "1.21");
+    assert_eq!(TargetVersion::default().pack_format(), super::save::DEFAULT_PACK_FORMAT);
+    assert_eq!(TargetVersion::new(16, 1).pack_format(), 5);
+    assert!(version.supports(Feature::ReturnCommand));
+    assert!(!TargetVersion::new(20, 2).supports(Feature::ReturnCommand));
+    assert!(TargetVersion::new(20, 2).supports(Feature::FunctionMacros));
+    assert_eq!("1.21.5".parse::<TargetVersion>().unwrap().pack_format(), 71);
+    // The versions newer than the known ones use the last pack format known
+    let future = "1.22".parse::<TargetVersion>().unwrap();
+    assert_eq!((future.pack_format(), future.is_known()), (71, false));
+    assert!(TargetVersion::NEWEST.is_known());
+    for invalid in ["1.12.2", "2.0", "1.x", "1"] {
+        assert!(invalid.parse::<TargetVersion>().is_err(), "`{}` shouldn't be parsed", invalid);
+    }
+}
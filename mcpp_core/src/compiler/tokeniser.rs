@@ -1,8 +1,101 @@
-use super::Token;
+use super::{Span, Spanned, SpannedToken, Token};
 
 const WHITESPACE:[char; 4] = [' ', '\n', '\t', '\r'];
 const NUMERIC:[char; 10] = ['0', '1', '2', '3', '4', '5', '6', '7', '8', '9'];
 
+/// Chars of the source with their byte offsets
+#[derive(Clone)]
+struct Cursor<'a> {
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+    len: usize
+}
+impl<'a> From<&'a str> for Cursor<'a> {
+    fn from(value: &'a str) -> Self {
+        Cursor { chars: value.char_indices().peekable(), len: value.len() }
+    }
+}
+impl Iterator for Cursor<'_> {
+    type Item = char;
+    fn next(&mut self) -> Option<char> {
+        self.chars.next().map(|(_, c)| c)
+    }
+}
+impl Cursor<'_> {
+    fn peek(&mut self) -> Option<&char> {
+        self.chars.peek().map(|(_, c)| c)
+    }
+    fn next_if(&mut self, func:impl FnOnce(&char) -> bool) -> Option<char> {
+        self.chars.next_if(|(_, c)| func(c)).map(|(_, c)| c)
+    }
+    /// The byte offset of the next char
+    fn offset(&mut self) -> usize {
+        let len = self.len;
+        self.chars.peek().map_or(len, |(i, _)| *i)
+    }
+}
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexError {
+    UnclosedString(Span),
+    UnclosedBlockComment(Span),
+    MalformedNumber(String, Span),
+    IntegerOverflow(String, Span),
+    StrayDollar(Span),
+    UnknownCharacter(char, Span)
+}
+impl LexError {
+    pub fn span(&self) -> Span {
+        match self {
+            LexError::UnclosedString(s) |
+            LexError::UnclosedBlockComment(s) |
+            LexError::MalformedNumber(_, s) |
+            LexError::IntegerOverflow(_, s) |
+            LexError::StrayDollar(s) |
+            LexError::UnknownCharacter(_, s) => *s
+        }
+    }
+    /// The description of the error without its location
+    pub fn message(&self) -> String {
+        match self {
+            LexError::UnclosedString(_) => String::from("the string literal is never closed"),
+            LexError::UnclosedBlockComment(_) => String::from("the block comment is never closed"),
+            LexError::MalformedNumber(n, _) => format!("`{}` is not a valid number", n),
+            LexError::IntegerOverflow(n, _) => format!("the integer `{}` doesn't fit in int, whose range is {} to {}", n, i32::MIN, i32::MAX),
+            LexError::StrayDollar(_) => String::from("`$` must be followed by a Minecraft identifier"),
+            LexError::UnknownCharacter(c, _) => format!("unknown character `{}`", c)
+        }
+    }
+}
+impl std::fmt::Display for LexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.span(), self.message())
+    }
+}
+
+/// Converts byte offsets into lines and columns
+pub struct Locator<'a> {
+    input: &'a str,
+    line_starts: Vec<usize>
+}
+impl<'a> From<&'a str> for Locator<'a> {
+    fn from(value: &'a str) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(value.match_indices('\n').map(|(i, _)| i + 1));
+        Locator { input: value, line_starts }
+    }
+}
+impl Locator<'_> {
+    pub fn span(&self, start:usize, end:usize) -> Span {
+        let line = self.line_starts.partition_point(|l| *l <= start);
+        let line_start = self.line_starts[line - 1];
+        Span {
+            start,
+            end,
+            line,
+            column: self.input[line_start..start].chars().count() + 1
+        }
+    }
+}
+
 fn delimiter(input:&char) -> Option<Token> {
     match input  {
         '.' => Some(Token::Dot),
@@ -20,8 +113,8 @@ fn delimiter(input:&char) -> Option<Token> {
 }
 fn operator(current_char:&char, next_char:Option<&char>) -> Option<(Token, bool)> {
     // Two chars
-    if let Some(next) = next_char {
-        match match format!("{}{}", current_char, next).as_str() {
+    if let Some(next) = next_char
+        && let Some(s) = match format!("{}{}", current_char, next).as_str() {
             // Arrows
             "->" => Some(Token::Arr),
             "=>" => Some(Token::FArr),
@@ -34,12 +127,10 @@ fn operator(current_char:&char, next_char:Option<&char>) -> Option<(Token, bool)
 
             _ => None
         } {
-            Some(s) => return Some((s, true)),
-            None => ()
-        }
+        return Some((s, true));
     }
     // Single char
-    match match current_char {
+    match current_char {
         // Operator
         '=' => Some(Token::Asn),
         '+' => Some(Token::Add),
@@ -58,25 +149,32 @@ fn operator(current_char:&char, next_char:Option<&char>) -> Option<(Token, bool)
         '&' => Some(Token::And),
 
         _   => None
-    } {
-        Some(s) => return Some((s, false)),
-        None => None
-    }
+    }.map(|s| (s, false))
+}
+fn is_identifier_char(input:&char) -> bool {
+    input.is_alphanumeric() || *input == '_'
 }
 /// It returns identifier or keyword
 fn solve_a_word(input:&str) -> Token {
     match input {
         "let"   => Token::Let,
+        "as"    => Token::As,
         "fn"    => Token::Fn,
         "if"    => Token::If,
         "else"  => Token::Else,
         "while" => Token::While,
         "for"   => Token::For,
+        "in"    => Token::In,
+        "step"  => Token::Step,
         "int"   => Token::IntType,
         "float" => Token::FltType,
+        "fixed" => Token::FltType,
         "bool"  => Token::BlnType,
+        "str"   => Token::StrType,
         "none"  => Token::NoneType,
         "return"=> Token::Return,
+        "break" => Token::Break,
+        "continue" => Token::Continue,
         "true"  => Token::Bln(true),
         "false" => Token::Bln(false),
         _       => Token::Ident(input.to_string())
@@ -87,72 +185,194 @@ fn solve_a_word(input:&str) -> Token {
 fn tokenizer_test() {
     println!("{:?}", tokenize("fn main -> int {let a = 1.14 + 5.14; return 810;}".to_string()))
 }
-pub fn tokenize(input:String) -> Vec<Token> {
+#[test]
+fn lex_error_test() {
+    assert_eq!(tokens_of("let a1 = b_2;")[1], Token::Ident("a1".to_string()));
+    let errors = tokenize("let a = 99999999999;\nlet b = 1.2.3 + 4x;\n$ @ \"abc".to_string()).unwrap_err();
+    let found = errors
+        .iter()
+        .map(|e| e.to_string())
+        .collect::<Vec<String>>();
+    assert_eq!(found, vec![
+        "1:9: the integer `99999999999` doesn't fit in int, whose range is -2147483648 to 2147483647",
+        "2:9: `1.2.3` is not a valid number",
+        "2:17: `4x` is not a valid number",
+        "3:1: `$` must be followed by a Minecraft identifier",
+        "3:3: unknown character `@`",
+        "3:5: the string literal is never closed"
+    ]);
+    assert_eq!(
+        tokenize("/* /* */".to_string()).unwrap_err(),
+        vec![LexError::UnclosedBlockComment(Locator::from("/* /* */").span(0, 8))]
+    );
+}
+#[cfg(test)]
+fn tokens_of(input:&str) -> Vec<Token> {
+    tokenize(input.to_string()).unwrap().into_iter().map(|t| t.inner).collect()
+}
+#[test]
+fn comment_test() {
+    assert_eq!(
+        tokens_of("let a = // 15;\n17; /* b /* nested */ = 2; */ a / 2;"),
+        tokens_of("let a = 17; a / 2;")
+    );
+    assert_eq!(
+        tokens_of("/// The answer\n//// Not a doc\nlet a = 42;")[..2],
+        [Token::DocComment("The answer".to_string()), Token::Let]
+    );
+}
+#[test]
+fn range_test() {
+    assert_eq!(
+        tokens_of("0..n 1..=2.5"),
+        [Token::Int(0), Token::DotDot, Token::Ident("n".to_string()), Token::Int(1), Token::DotDotEq, Token::Flt(2.5)]
+    );
+}
+#[test]
+fn label_test() {
+    assert_eq!(
+        tokens_of("'outer: while a { break 'outer; }")[..3],
+        [Token::Label("outer".to_string()), Token::Colon, Token::While]
+    );
+    assert_eq!(tokens_of("break 'outer;")[1], Token::Label("outer".to_string()));
+}
+#[test]
+fn span_test() {
+    let tokens = tokenize("let あ = 1.5;\n  a >= 2;".to_string()).unwrap();
+    let spans = tokens
+        .iter()
+        .map(|t| (t.span.start, t.span.end, t.span.line, t.span.column))
+        .collect::<Vec<(usize, usize, usize, usize)>>();
+    assert_eq!(spans, vec![
+        (0, 3, 1, 1), (4, 7, 1, 5), (8, 9, 1, 7), (10, 13, 1, 9), (13, 14, 1, 12),
+        (17, 18, 2, 3), (19, 21, 2, 5), (22, 23, 2, 8), (23, 24, 2, 9)
+    ]);
+}
+pub fn tokenize(input:String) -> Result<Vec<SpannedToken>, Vec<LexError>> {
+    let locator = Locator::from(input.as_str());
+    let mut errors = Vec::new();
     let mut queue = String::new();
-    let mut chars = input.chars().peekable();
-    let mut tokens:Vec<Token> = Vec::new();
+    let mut queue_start = 0;
+    let mut chars = Cursor::from(input.as_str());
+    let mut tokens:Vec<(Token, usize, usize)> = Vec::new();
 
-    let flush_queue = |q: &mut String, ts: &mut Vec<Token>| {
+    let flush_queue = |q: &mut String, ts: &mut Vec<(Token, usize, usize)>, start: usize, end: usize| {
         if !q.is_empty() {
-            ts.push(solve_a_word(q));
+            ts.push((solve_a_word(q), start, end));
             q.clear();
         }
     };
 
     while let Some(cur_char) = chars.peek().cloned() { // peekして、処理後にnextする戦略
+        let start = chars.offset();
 
         // 1. 空白文字か？
         if WHITESPACE.contains(&cur_char) {
-            flush_queue(&mut queue, &mut tokens); // 空白の前が識別子なら確定
+            flush_queue(&mut queue, &mut tokens, queue_start, start); // 空白の前が識別子なら確定
             chars.next(); // 空白を消費
             continue;
         }
 
-        // 2. 区切り文字か？
+        // 2. 区切り文字か？ ('..' と '..=' は範囲)
+        if cur_char == '.' && chars.clone().nth(1) == Some('.') {
+            flush_queue(&mut queue, &mut tokens, queue_start, start); // 範囲の前が識別子なら確定
+            chars.next(); // 1つ目の '.' を消費
+            chars.next(); // 2つ目の '.' を消費
+            let token = if chars.next_if(|c| *c == '=').is_some() {Token::DotDotEq} else {Token::DotDot};
+            tokens.push((token, start, chars.offset()));
+            continue;
+        }
+
         if let Some(token) = delimiter(&cur_char) {
-            flush_queue(&mut queue, &mut tokens); // 区切り文字の前が識別子なら確定
-            tokens.push(token);
+            flush_queue(&mut queue, &mut tokens, queue_start, start); // 区切り文字の前が識別子なら確定
             chars.next(); // 区切り文字を消費
+            tokens.push((token, start, chars.offset()));
             continue;
         }
 
-        // 3. 演算子か？ (次の文字も考慮)
+        // 3. コメントか？ (// ... , /* ... */ , /// ...)
         let next_char_peek = chars.clone().nth(1); // peek().peek() のようなこと
+        if cur_char == '/' && next_char_peek == Some('/') {
+            flush_queue(&mut queue, &mut tokens, queue_start, start); // コメントの前が識別子なら確定
+            chars.next(); // 最初の '/' を消費
+            chars.next(); // 2番目の '/' を消費
+            // '///' はドキュメントコメントとしてトークンに残す ('////' は通常のコメント)
+            let is_doc = chars.peek() == Some(&'/') && chars.clone().nth(1) != Some('/');
+            if is_doc {
+                chars.next(); // 3番目の '/' を消費
+            }
+            let mut comment = String::new();
+            while let Some(comment_char) = chars.next_if(|c| *c != '\n') {
+                comment.push(comment_char);
+            }
+            if is_doc {
+                tokens.push((Token::DocComment(comment.trim().to_string()), start, chars.offset()));
+            }
+            continue;
+        }
+        if cur_char == '/' && next_char_peek == Some('*') {
+            flush_queue(&mut queue, &mut tokens, queue_start, start); // コメントの前が識別子なら確定
+            chars.next(); // '/' を消費
+            chars.next(); // '*' を消費
+            // ブロックコメントは入れ子にできる
+            let mut depth = 1;
+            while depth > 0 {
+                match (chars.next(), chars.peek()) {
+                    (Some('/'), Some('*')) => {
+                        chars.next();
+                        depth += 1;
+                    },
+                    (Some('*'), Some('/')) => {
+                        chars.next();
+                        depth -= 1;
+                    },
+                    (Some(_), _) => (),
+                    (None, _) => {
+                        // エラー処理: ブロックコメントが閉じられていない
+                        errors.push(LexError::UnclosedBlockComment(locator.span(start, chars.offset())));
+                        break;
+                    }
+                }
+            }
+            continue;
+        }
+
+        // 4. 演算子か？ (次の文字も考慮)
         if let Some((token, consumed_next)) = operator(&cur_char, next_char_peek.as_ref()) {
-            flush_queue(&mut queue, &mut tokens); // 演算子の前が識別子なら確定
-            tokens.push(token);
+            flush_queue(&mut queue, &mut tokens, queue_start, start); // 演算子の前が識別子なら確定
             chars.next(); // 演算子の最初の文字を消費
             if consumed_next {
                 chars.next(); // 演算子の2番目の文字も消費
             }
+            tokens.push((token, start, chars.offset()));
             continue;
         }
 
-        // 4. 文字列リテラルか？ (")
+        // 5. 文字列リテラルか？ (")
         if cur_char == '"' {
-            flush_queue(&mut queue, &mut tokens); // 文字列リテラルの前が識別子なら確定 (通常はないはずだが念のため)
+            flush_queue(&mut queue, &mut tokens, queue_start, start); // 文字列リテラルの前が識別子なら確定 (通常はないはずだが念のため)
             chars.next(); // 開始の " を消費
             let mut inside = String::new();
             let mut closed = false;
-            while let Some(string_char) = chars.next() {
+            for string_char in chars.by_ref() {
                 if string_char == '"' {
                     closed = true;
                     break; // 終了の " を見つけたらループを抜ける
                 }
                 inside.push(string_char);
             }
-            if !closed {
+            if closed {
+                tokens.push((Token::Str(inside), start, chars.offset()));
+            } else {
                 // エラー処理: 文字列が閉じられていない
-                println!("Warning: Unclosed string literal encountered.");
-                // tokens.push(Token::Error("Unclosed string literal".to_string()));
+                errors.push(LexError::UnclosedString(locator.span(start, chars.offset())));
             }
-            tokens.push(Token::Str(inside));
             continue;
         }
 
-        // 5. MCID リテラルか？ ($) - $の後の識別子を読む想定
+        // 6. MCID リテラルか？ ($) - $の後の識別子を読む想定
         if cur_char == '$' {
-            flush_queue(&mut queue, &mut tokens); // MCIDの前が識別子なら確定
+            flush_queue(&mut queue, &mut tokens, queue_start, start); // MCIDの前が識別子なら確定
             chars.next(); // '$' を消費
             let mut mc_id = String::new();
             while let Some(next_peek) = chars.peek() {
@@ -169,20 +389,37 @@ pub fn tokenize(input:String) -> Vec<Token> {
                 }
             }
             if !mc_id.is_empty() {
-                tokens.push(Token::MCId(mc_id));
+                tokens.push((Token::MCId(mc_id), start, chars.offset()));
+            } else {
+                // エラー処理: '$' の後に識別子が続かない
+                errors.push(LexError::StrayDollar(locator.span(start, chars.offset())));
+            }
+            continue;
+        }
+
+        // 7. ラベルか？ (') - ループの名前 'name
+        if cur_char == '\'' {
+            flush_queue(&mut queue, &mut tokens, queue_start, start); // ラベルの前が識別子なら確定
+            chars.next(); // '\'' を消費
+            let mut label = String::new();
+            while let Some(label_char) = chars.next_if(is_identifier_char) {
+                label.push(label_char);
+            }
+            if !label.is_empty() {
+                tokens.push((Token::Label(label), start, chars.offset()));
             } else {
-                 // エラー処理: '$' の後に識別子が続かない
-                println!("Warning: '$' not followed by an identifier.");
-                 // tokens.push(Token::Error("Expected identifier after $".to_string()));
+                // エラー処理: '\'' の後に名前が続かない
+                errors.push(LexError::UnknownCharacter('\'', locator.span(start, chars.offset())));
             }
             continue;
         }
 
-        // 6. 数値リテラルか？ (数字で始まる)
-        if NUMERIC.contains(&cur_char) {
-            flush_queue(&mut queue, &mut tokens); // 数値の前が識別子なら確定 (通常はない)
+        // 8. 数値リテラルか？ (数字で始まる、識別子の途中の数字は除く)
+        if NUMERIC.contains(&cur_char) && queue.is_empty() {
+            flush_queue(&mut queue, &mut tokens, queue_start, start); // 数値の前が識別子なら確定 (通常はない)
             let mut numeric = String::new();
             let mut is_float = false;
+            let mut is_malformed = false;
             numeric.push(chars.next().unwrap()); // 最初の数字を消費
 
             while let Some(next_peek) = chars.peek() {
@@ -192,48 +429,71 @@ pub fn tokenize(input:String) -> Vec<Token> {
                      // '.' の次が数字かどうかで float か判断
                      let mut temp_chars = chars.clone(); // イテレータをコピーして先読み
                      temp_chars.next(); // '.' を仮想的に消費
-                    if temp_chars.peek().map_or(false, |c| NUMERIC.contains(c)) {
+                    if temp_chars.peek().is_some_and(|c| NUMERIC.contains(c)) {
                           // '.' の次が数字なら float
-                          if is_float { // すでに '.' が含まれている場合 (例: 1.2.3)
-                                println!("Warning: Multiple '.' in numeric literal '{}'", numeric);
-                               break; // 不正な数値としてここで終了
+                        if is_float { // すでに '.' が含まれている場合 (例: 1.2.3)
+                            is_malformed = true; // 不正な数値として最後まで読む
                         }
-                          numeric.push(chars.next().unwrap()); // '.' を消費して追加
+                        numeric.push(chars.next().unwrap()); // '.' を消費して追加
                         is_float = true;
                     } else {
                         // '.' の次が数字でない -> '.' は数値の一部ではない
                         break;
                     }
+                } else if is_identifier_char(next_peek) {
+                    // 数字の直後の文字 (例: 4x) も不正な数値の一部とする
+                    is_malformed = true;
+                    numeric.push(chars.next().unwrap());
                 } else {
                      // 数字でも '.' でもないなら数値リテラル終了
                     break;
                 }
             }
 
-            if is_float {
-                match numeric.parse::<f32>() {
-                    Ok(f) => tokens.push(Token::Flt(f)),
-                    Err(e) => println!("Error parsing float '{}': {}", numeric, e),
-                    // tokens.push(Token::Error(format!("Invalid float: {}", numeric)))
+            let span = locator.span(start, chars.offset());
+            if is_malformed {
+                errors.push(LexError::MalformedNumber(numeric, span));
+            } else if is_float {
+                match numeric.parse::<f64>() {
+                    Ok(f) => tokens.push((Token::Flt(f), start, chars.offset())),
+                    Err(_) => errors.push(LexError::MalformedNumber(numeric, span))
                 }
             } else {
+                // 数字だけなので、失敗するのは i32 に収まらないときだけ
                 match numeric.parse::<i32>() {
-                    Ok(i) => tokens.push(Token::Int(i)),
-                    Err(e) => println!("Error parsing int '{}': {}", numeric, e),
-                    // tokens.push(Token::Error(format!("Invalid int: {}", numeric)))
+                    Ok(i) => tokens.push((Token::Int(i), start, chars.offset())),
+                    Err(_) => errors.push(LexError::IntegerOverflow(numeric, span))
                 }
             }
             continue;
         }
 
-        // 7. 上記のいずれでもなければ、識別子/キーワードの一部
+        // 9. 識別子に使えない文字か？
+        if !is_identifier_char(&cur_char) {
+            flush_queue(&mut queue, &mut tokens, queue_start, start); // 不明な文字の前が識別子なら確定
+            chars.next(); // 不明な文字を消費
+            errors.push(LexError::UnknownCharacter(cur_char, locator.span(start, chars.offset())));
+            continue;
+        }
+
+        // 10. 上記のいずれでもなければ、識別子/キーワードの一部
         // (cur_char は peek() で見ただけなのでここで消費する)
+        if queue.is_empty() {
+            queue_start = start;
+        }
         queue.push(chars.next().unwrap());
 
     } // while let Some(cur_char) = chars.peek().cloned()
 
     // ループ終了後、queue に残っている最後の識別子/キーワードを処理
-    flush_queue(&mut queue, &mut tokens);
+    flush_queue(&mut queue, &mut tokens, queue_start, input.len());
 
-    tokens
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    // バイトオフセットから行と列を求める
+    Ok(tokens
+        .into_iter()
+        .map(|(token, start, end)| Spanned::new(token, locator.span(start, end)))
+        .collect())
 }
\ No newline at end of file
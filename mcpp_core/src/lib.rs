@@ -1,16 +1,36 @@
 pub mod compiler;
-use compiler::{ast::{serialiser::{MCFunction}, syntax_analyser}, CompileError, Compiler, Token};
-use crate::compiler::ast::serialiser::MCFunctionizable;
+pub mod vm;
+use compiler::{ast::{serialiser::{MCFunction}, syntax_analyser, SyntaxError}, CompileError, Compiler, Spanned, Token};
 pub use compiler::{evaluater, tokeniser};
 
 pub fn compile(input:&str) -> Result<Vec<MCFunction>, CompileError> {
-    let mut inside = vec![Token::LBrace];
-    inside.extend(tokeniser::tokenize(input.to_string()));
-    inside.extend(vec![Token::RBrace]);
+    compile_in("MCPP", input)
+}
+/// Compiles the source into the mcfunctions of the namespace.
+/// The first one is the main and the last one is the load function, which has to run before the others.
+pub fn compile_in(namespace:&str, input:&str) -> Result<Vec<MCFunction>, CompileError> {
+    compile_with(Compiler::from(namespace), input)
+}
+/// Compiles the source with the compiler, e.g. one which has some macros registered
+pub fn compile_with(mut compiler:Compiler, input:&str) -> Result<Vec<MCFunction>, CompileError> {
+    // The whole source is a block, whose braces are placed at its both ends
+    let locator = tokeniser::Locator::from(input);
+    let tokens = tokeniser::tokenize(input.to_string())?;
+    if let Some(span) = syntax_analyser::unclosed_brace(&tokens) {
+        return Err(CompileError::from(SyntaxError::UnbalancedBraces(span)));
+    }
+    let mut inside = vec![Spanned::new(Token::LBrace, locator.span(0, 0))];
+    inside.extend(tokens);
+    inside.push(Spanned::new(Token::RBrace, locator.span(input.len(), input.len())));
     let mut analyser = syntax_analyser::SyntaxAnalyser::from(inside);
-    let mut compiler = Compiler::from("MCPP");
-    Ok(vec![match analyser.get_block() {
+    let main = match analyser.get_block() {
         Ok(o) => o,
-        Err(e) => Err(CompileError::ASyntaxErrorOccured(e))?
-    }.mcfunctionate(&mut compiler)?])
+        Err(e) => Err(CompileError::from(e))?
+    }.compile_named("main".to_string(), &mut compiler)?;
+    compiler.check_recursion()?;
+    let load = compiler.load_function();
+    let mut mcfunctions = vec![main];
+    mcfunctions.extend(compiler.compiled);
+    mcfunctions.push(load);
+    Ok(mcfunctions)
 }
\ No newline at end of file
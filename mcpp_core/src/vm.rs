@@ -0,0 +1,514 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::compiler::ast::serialiser::MCFunction;
+
+/// The default of the gamerule `maxCommandChainLength`
+pub const DEFAULT_COMMAND_LIMIT:usize = 65536;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VMError {
+    UnknownCommand(String),
+    MalformedCommand(String),
+    UnknownFunction(String),
+    UnknownObjective(String),
+    CommandLimitExceeded(usize)
+}
+impl VMError {
+    pub fn message(&self) -> String {
+        match self {
+            Self::UnknownCommand(c) => format!("unknown command `{}`", c),
+            Self::MalformedCommand(c) => format!("malformed command `{}`", c),
+            Self::UnknownFunction(f) => format!("unknown function `{}`", f),
+            Self::UnknownObjective(o) => format!("unknown scoreboard objective `{}`", o),
+            Self::CommandLimitExceeded(l) => format!("more than {} commands were executed", l)
+        }
+    }
+}
+impl fmt::Display for VMError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+/// A value of the data storage
+#[derive(Debug, Clone, PartialEq)]
+pub enum Nbt {
+    Str(String),
+    Int(i32),
+    Double(f64)
+}
+impl Nbt {
+    /// Parses a SNBT value such as `"Steve"` or `5`
+    fn parse(snbt:&str) -> Option<Nbt> {
+        match snbt.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+            Some(quoted) => Some(Nbt::Str(quoted.replace("\\\"", "\"").replace("\\\\", "\\"))),
+            None => snbt
+                .parse()
+                .map(Nbt::Int)
+                .or_else(|_| snbt.trim_end_matches('d').parse().map(Nbt::Double))
+                .ok()
+        }
+    }
+}
+impl fmt::Display for Nbt {
+    /// The text a function macro puts in
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Nbt::Str(s) => write!(f, "{}", s),
+            Nbt::Int(i) => write!(f, "{}", i),
+            // As `DecimalFormat("#")` of Minecraft with 15 fraction digits, e.g. `1` for 1.0
+            Nbt::Double(d) => {
+                let formatted = format!("{:.15}", d);
+                write!(f, "{}", formatted.trim_end_matches('0').trim_end_matches('.'))
+            }
+        }
+    }
+}
+
+/// Executes mcfunctions against an in-memory scoreboard, with the integer semantics of Minecraft.
+/// Only the commands the compiler emits are supported.
+pub struct VirtualMachine {
+    functions: HashMap<String, Vec<String>>,
+    objectives: HashSet<String>,
+    scores: HashMap<(String, String), i32>,
+    /// The values of the data storages keyed by the storage and the path, e.g. `main.name`
+    storage: HashMap<(String, String), Nbt>,
+    /// The last value of every score, which is kept even after it's reset
+    last_scores: HashMap<(String, String), i32>,
+    command_limit: usize,
+    executed: usize,
+    /// The result of `return` run in the current function, which stops it
+    returned: Option<Option<i32>>
+}
+
+#[test]
+fn arithmetic_test() {
+    let mut vm = VirtualMachine::new().command_limit(100);
+    let run = |vm:&mut VirtualMachine, commands:&[&str]| {
+        for command in commands {
+            vm.execute(command).unwrap();
+        }
+    };
+    run(&mut vm, &["scoreboard objectives add o dummy", "scoreboard players set #a o -7", "scoreboard players set #b o 2"]);
+    // Division rounds toward negative infinity, and the remainder has the sign of the divisor
+    run(&mut vm, &["scoreboard players operation #a o /= #b o"]);
+    assert_eq!(vm.score("#a", "o"), Some(-4));
+    run(&mut vm, &["scoreboard players set #a o -7", "scoreboard players operation #a o %= #b o"]);
+    assert_eq!(vm.score("#a", "o"), Some(1));
+    run(&mut vm, &["scoreboard players set #a o 7", "scoreboard players set #b o -2", "scoreboard players operation #a o %= #b o"]);
+    assert_eq!(vm.score("#a", "o"), Some(-1));
+    // A division by zero fails, leaving the score as it is
+    run(&mut vm, &["scoreboard players set #z o 0", "scoreboard players operation #a o /= #z o"]);
+    assert_eq!(vm.score("#a", "o"), Some(-1));
+    run(&mut vm, &["scoreboard players set #a o 2147483647", "scoreboard players add #a o 1"]);
+    assert_eq!(vm.score("#a", "o"), Some(i32::MIN));
+    run(&mut vm, &["scoreboard players operation #a o >< #b o", "scoreboard players reset #b o"]);
+    assert_eq!((vm.score("#a", "o"), vm.score("#b", "o"), vm.last_score("#b", "o")), (Some(-2), None, Some(i32::MIN)));
+
+    run(&mut vm, &[
+        "execute store success score #s o if score #a o < #z o",
+        "execute store success score #t o if score #unset o matches 0",
+        "execute if score #a o matches ..-2 run scoreboard players set #u o 1",
+        "execute unless score #a o matches -2 run scoreboard players set #v o 1"
+    ]);
+    assert_eq!((vm.score("#s", "o"), vm.score("#t", "o")), (Some(1), Some(0)));
+    assert_eq!((vm.score("#u", "o"), vm.score("#v", "o")), (Some(1), None));
+    assert!(matches!(vm.execute("scoreboard players add #a o -1"), Err(VMError::MalformedCommand(_))));
+    assert!(matches!(vm.execute("scoreboard players set #a p 1"), Err(VMError::UnknownObjective(_))));
+}
+
+#[test]
+fn nbt_test() {
+    // A function macro puts a double in without its point or the digits Java can't print
+    for (double, expected) in [(1.0, "1"), (-3.0, "-3"), (2.5, "2.5"), (-0.125, "-0.125"), (0.1 + 0.2, "0.3"), (1e20, "100000000000000000000")] {
+        assert_eq!(Nbt::Double(double).to_string(), expected);
+    }
+    assert_eq!(Nbt::parse("2.5d").map(|n| n.to_string()), Some("2.5".to_string()));
+}
+#[test]
+fn program_test() {
+    let compiled = crate::compile("
+        fn fact(n:int) -> int { let r = 1; while n > 1 { r = r * n; n = n - 1; } return r; }
+        let a = fact(5);
+        let b = 0;
+        while b < 10 { b = b + 3; }
+    ").unwrap();
+    let mut vm = VirtualMachine::new().functions("MCPP", &compiled);
+    vm.run("MCPP:__load").unwrap();
+    vm.run("MCPP:main").unwrap();
+    assert_eq!(vm.last_score("#main.a", "MCPP.var"), Some(120));
+    assert_eq!(vm.last_score("#main.b", "MCPP.var"), Some(12));
+    // The variables are reset on leaving their scope
+    assert_eq!(vm.score("#main.a", "MCPP.var"), None);
+
+    let mut vm = VirtualMachine::new();
+    vm.functions.insert("t:one".to_string(), vec!["return 1".to_string(), "scoreboard players set #x o 1".to_string()]);
+    vm.functions.insert("t:none".to_string(), vec!["scoreboard objectives add o dummy".to_string()]);
+    vm.functions.insert("t:fail".to_string(), vec!["return fail".to_string()]);
+    vm.functions.insert("t:outer".to_string(), vec![
+        "execute if function t:none run return 2".to_string(),
+        "execute unless function t:fail if function t:one run return run function t:one".to_string(),
+        "return 3".to_string()
+    ]);
+    // `return` stops the function, and a function without it fails `if function`
+    assert_eq!(vm.run("t:outer"), Ok(Some(1)));
+    assert_eq!(vm.score("#x", "o"), None);
+
+    let endless = crate::compile("while true {}").unwrap();
+    let mut vm = VirtualMachine::new().command_limit(1000).functions("MCPP", &endless);
+    vm.run("MCPP:__load").unwrap();
+    assert_eq!(vm.run("MCPP:main"), Err(VMError::CommandLimitExceeded(1000)));
+}
+
+impl Default for VirtualMachine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VirtualMachine {
+    pub fn new() -> Self {
+        VirtualMachine {
+            functions: HashMap::new(),
+            objectives: HashSet::new(),
+            scores: HashMap::new(),
+            storage: HashMap::new(),
+            last_scores: HashMap::new(),
+            command_limit: DEFAULT_COMMAND_LIMIT,
+            executed: 0,
+            returned: None
+        }
+    }
+    /// The number of the commands a run can execute, not to hang on an endless loop
+    pub fn command_limit(mut self, command_limit:usize) -> Self {
+        self.command_limit = command_limit;
+        self
+    }
+    /// Adds the mcfunctions into the namespace
+    pub fn functions(mut self, namespace:&str, mcfunctions:&[MCFunction]) -> Self {
+        for mcfunction in mcfunctions {
+            self.functions.insert(
+                format!("{}:{}", namespace, mcfunction.get_full_path()),
+                mcfunction.inside.lines().map(String::from).collect()
+            );
+        }
+        self
+    }
+    /// The current value of the score, which is None if it's not set
+    pub fn score(&self, player:&str, objective:&str) -> Option<i32> {
+        self.scores.get(&(player.to_string(), objective.to_string())).copied()
+    }
+    /// The value the score had at last, even if it was reset since then
+    pub fn last_score(&self, player:&str, objective:&str) -> Option<i32> {
+        self.last_scores.get(&(player.to_string(), objective.to_string())).copied()
+    }
+    /// The current value in the data storage such as `namespace:vars`
+    pub fn data(&self, storage:&str, path:&str) -> Option<&Nbt> {
+        self.storage.get(&(storage.to_string(), path.to_string()))
+    }
+    /// Runs the function such as `namespace:main` from the beginning of a tick
+    pub fn run(&mut self, function:&str) -> Result<Option<i32>, VMError> {
+        self.executed = 0;
+        self.call(function, None)
+    }
+    /// Executes a command, which returns its result or None if it failed
+    pub fn execute(&mut self, command:&str) -> Result<Option<i32>, VMError> {
+        self.executed += 1;
+        if self.executed > self.command_limit {
+            return Err(VMError::CommandLimitExceeded(self.command_limit));
+        }
+        let malformed = || VMError::MalformedCommand(command.to_string());
+        let words = command.split_whitespace().collect::<Vec<&str>>();
+        match words.as_slice() {
+            ["scoreboard", "objectives", "add", objective, "dummy", ..] => {
+                Ok(self.objectives.insert(objective.to_string()).then_some(self.objectives.len() as i32))
+            },
+            ["scoreboard", "players", "set", player, objective, value] => {
+                let value = value.parse().map_err(|_| malformed())?;
+                self.set(player, objective, value)?;
+                Ok(Some(value))
+            },
+            // It fails if the score isn't set
+            ["scoreboard", "players", "get", player, objective] => {
+                self.check_objective(objective)?;
+                Ok(self.score(player, objective))
+            },
+            ["scoreboard", "players", add_rem @ ("add" | "remove"), player, objective, value] => {
+                // Minecraft only accepts a non-negative amount
+                let value = value.parse::<i32>().ok().filter(|v| *v >= 0).ok_or_else(malformed)?;
+                let current = self.get_or_create(player, objective)?;
+                let value = if *add_rem == "add" {current.wrapping_add(value)} else {current.wrapping_sub(value)};
+                self.set(player, objective, value)?;
+                Ok(Some(value))
+            },
+            ["scoreboard", "players", "operation", left, left_objective, operator, right, right_objective] => {
+                let l = self.get_or_create(left, left_objective)?;
+                let r = self.get_or_create(right, right_objective)?;
+                let value = match *operator {
+                    "+=" => l.wrapping_add(r),
+                    "-=" => l.wrapping_sub(r),
+                    "*=" => l.wrapping_mul(r),
+                    "/=" | "%=" if r == 0 => return Ok(None),
+                    "/=" => floor_div(l, r),
+                    "%=" => l.wrapping_sub(floor_div(l, r).wrapping_mul(r)),
+                    "=" => r,
+                    "<" => l.min(r),
+                    ">" => l.max(r),
+                    "><" => {
+                        self.set(right, right_objective, l)?;
+                        r
+                    },
+                    _ => return Err(malformed())
+                };
+                self.set(left, left_objective, value)?;
+                Ok(Some(value))
+            },
+            ["scoreboard", "players", "reset", player, objective] => {
+                self.check_objective(objective)?;
+                Ok(self.scores.remove(&(player.to_string(), objective.to_string())).map(|_| 1))
+            },
+            ["data", "modify", "storage", storage, path, "set", "value", ..] => {
+                let value = command.splitn(8, ' ').nth(7).and_then(Nbt::parse).ok_or_else(malformed)?;
+                Ok(self.set_data(storage, path, value))
+            },
+            // It fails if the source doesn't exist
+            ["data", "modify", "storage", storage, path, "set", "from", "storage", source, source_path] => {
+                match self.data(source, source_path).cloned() {
+                    Some(value) => Ok(self.set_data(storage, path, value)),
+                    None => Ok(None)
+                }
+            },
+            ["data", "get", "storage", storage, path] => Ok(self.data(storage, path).map(|v| match v {
+                Nbt::Str(s) => s.chars().count() as i32,
+                Nbt::Int(i) => *i,
+                Nbt::Double(d) => d.floor() as i32
+            })),
+            // The compound is removed with the values inside
+            ["data", "remove", "storage", storage, path] => {
+                let before = self.storage.len();
+                let inside = format!("{}.", path);
+                self.storage.retain(|(s, p), _| s != storage || (p != path && !p.starts_with(&inside)));
+                Ok((self.storage.len() < before).then_some(1))
+            },
+            ["function", function] => self.call(function, None),
+            // The values directly inside the compound are the arguments of the function macro
+            ["function", function, "with", "storage", storage, path] => {
+                let inside = format!("{}.", path);
+                let arguments = self.storage
+                    .iter()
+                    .filter(|((s, p), _)| s == storage && p.strip_prefix(&inside).is_some_and(|k| !k.contains('.')))
+                    .map(|((_, p), v)| (p[inside.len()..].to_string(), v.to_string()))
+                    .collect::<HashMap<String, String>>();
+                self.call(function, Some(&arguments))
+            },
+            ["execute", ..] => self.run_execute(command.trim_start().trim_start_matches("execute")),
+            // They stop the function they're run in
+            ["return", "fail"] => {
+                self.returned = Some(None);
+                Ok(None)
+            },
+            ["return", "run", ..] => {
+                let result = self.execute(command.trim_start().splitn(3, ' ').nth(2).unwrap_or_default())?;
+                self.returned = Some(result);
+                Ok(result)
+            },
+            ["return", value] => {
+                let value = value.parse().map_err(|_| malformed())?;
+                self.returned = Some(Some(value));
+                Ok(Some(value))
+            },
+            _ => Err(VMError::UnknownCommand(command.to_string()))
+        }
+    }
+    fn call(&mut self, function:&str, arguments:Option<&HashMap<String, String>>) -> Result<Option<i32>, VMError> {
+        let commands = self
+            .functions
+            .get(function)
+            .ok_or(VMError::UnknownFunction(function.to_string()))?
+            .clone();
+        for command in commands.iter().map(|c| c.trim()) {
+            if command.is_empty() || command.starts_with('#') {
+                continue;
+            }
+            match command.strip_prefix('$') {
+                Some(line) => {
+                    let expanded = expand_macro(line, arguments).ok_or(VMError::MalformedCommand(command.to_string()))?;
+                    self.execute(&expanded)?
+                },
+                None => self.execute(command)?
+            };
+            if let Some(result) = self.returned.take() {
+                return Ok(result);
+            }
+        }
+        Ok(None)
+    }
+    /// Sets the value, which fails if nothing has changed
+    fn set_data(&mut self, storage:&str, path:&str, value:Nbt) -> Option<i32> {
+        let previous = self.storage.insert((storage.to_string(), path.to_string()), value.clone());
+        (previous != Some(value)).then_some(1)
+    }
+    fn run_execute(&mut self, mut rest:&str) -> Result<Option<i32>, VMError> {
+        let whole = format!("execute{}", rest);
+        let malformed = || VMError::MalformedCommand(whole.clone());
+        let mut stores = Vec::new();
+        let result = loop {
+            match next_word(&mut rest) {
+                Some(w @ ("if" | "unless")) => {
+                    let passed = self.condition(&mut rest).ok_or_else(malformed)?? == (w == "if");
+                    match (passed, rest.trim().is_empty()) {
+                        (true, true) => break Some(1),
+                        // A failing condition at the end makes the command fail, otherwise stops it
+                        (false, true) => break None,
+                        (false, false) => return Ok(None),
+                        (true, false) => ()
+                    }
+                },
+                Some("store") => {
+                    let kind = next_word(&mut rest).filter(|k| matches!(*k, "success" | "result"));
+                    match (kind, next_word(&mut rest), next_word(&mut rest), next_word(&mut rest)) {
+                        (Some(kind), Some("score"), Some(player), Some(objective)) => {
+                            stores.push(Store::Score(kind == "success", player, objective))
+                        },
+                        (Some(kind), Some("storage"), Some(storage), Some(path)) => {
+                            let (Some(datatype @ ("int" | "double")), Some(scale)) = (next_word(&mut rest), next_word(&mut rest)) else {
+                                return Err(malformed());
+                            };
+                            let scale = scale.parse().map_err(|_| malformed())?;
+                            stores.push(Store::Storage(kind == "success", storage, path, datatype == "double", scale))
+                        },
+                        _ => return Err(malformed())
+                    }
+                },
+                Some("run") => break self.execute(rest.trim())?,
+                _ => return Err(malformed())
+            }
+        };
+        for store in stores {
+            let (Store::Score(is_success, ..) | Store::Storage(is_success, ..)) = store;
+            let value = if is_success {result.is_some() as i32} else {result.unwrap_or(0)};
+            match store {
+                Store::Score(_, player, objective) => self.set(player, objective, value)?,
+                Store::Storage(_, storage, path, is_double, scale) => {
+                    let scaled = value as f64 * scale;
+                    self.set_data(storage, path, if is_double {Nbt::Double(scaled)} else {Nbt::Int(scaled as i32)});
+                }
+            }
+        }
+        Ok(result)
+    }
+    /// Tests `score <player> <objective> ...` of `if` and `unless`, which is false if a score isn't set,
+    /// or `function <function>`, which is true if the function returns other than 0
+    fn condition(&mut self, rest:&mut &str) -> Option<Result<bool, VMError>> {
+        if rest.trim_start().starts_with("function ") {
+            next_word(rest);
+            let function = next_word(rest)?;
+            return Some(self.call(function, None).map(|r| r.is_some_and(|r| r != 0)));
+        }
+        let (Some("score"), Some(player), Some(objective), Some(comparison), Some(right)) = (
+            next_word(rest), next_word(rest), next_word(rest), next_word(rest), next_word(rest)
+        ) else {
+            return None;
+        };
+        if let Err(e) = self.check_objective(objective) {
+            return Some(Err(e));
+        }
+        let left = self.score(player, objective);
+        if comparison == "matches" {
+            let (min, max) = parse_range(right)?;
+            return Some(Ok(left.is_some_and(|l| min <= l && l <= max)));
+        }
+        let right_objective = next_word(rest)?;
+        if let Err(e) = self.check_objective(right_objective) {
+            return Some(Err(e));
+        }
+        let (Some(l), Some(r)) = (left, self.score(right, right_objective)) else {
+            return Some(Ok(false));
+        };
+        Some(Ok(match comparison {
+            "<" => l < r,
+            "<=" => l <= r,
+            "=" => l == r,
+            ">=" => l >= r,
+            ">" => l > r,
+            _ => return None
+        }))
+    }
+    fn check_objective(&self, objective:&str) -> Result<(), VMError> {
+        if self.objectives.contains(objective) {
+            Ok(())
+        } else {
+            Err(VMError::UnknownObjective(objective.to_string()))
+        }
+    }
+    fn get_or_create(&mut self, player:&str, objective:&str) -> Result<i32, VMError> {
+        self.check_objective(objective)?;
+        match self.score(player, objective) {
+            Some(s) => Ok(s),
+            None => self.set(player, objective, 0).map(|_| 0)
+        }
+    }
+    fn set(&mut self, player:&str, objective:&str, value:i32) -> Result<(), VMError> {
+        self.check_objective(objective)?;
+        let key = (player.to_string(), objective.to_string());
+        self.scores.insert(key.clone(), value);
+        self.last_scores.insert(key, value);
+        Ok(())
+    }
+}
+
+/// Where `execute store` stores the result
+enum Store<'a> {
+    /// Whether it's the success, the player and the objective
+    Score(bool, &'a str, &'a str),
+    /// Whether it's the success, the storage, the path, whether it's a double and the scale
+    Storage(bool, &'a str, &'a str, bool, f64)
+}
+
+/// Puts the arguments into `$(name)` of a line of a function macro, which fails if any of them is missing
+fn expand_macro(line:&str, arguments:Option<&HashMap<String, String>>) -> Option<String> {
+    let arguments = arguments?;
+    let mut expanded = String::new();
+    let mut rest = line;
+    while let Some((before, after)) = rest.split_once("$(") {
+        let (name, after) = after.split_once(')')?;
+        expanded.push_str(before);
+        expanded.push_str(arguments.get(name)?);
+        rest = after;
+    }
+    expanded.push_str(rest);
+    Some(expanded)
+}
+
+/// Takes the next word of the command, leaving the rest as it is
+fn next_word<'a>(rest:&mut &'a str) -> Option<&'a str> {
+    let trimmed = rest.trim_start();
+    if trimmed.is_empty() {
+        return None;
+    }
+    let (word, remaining) = trimmed.split_once(' ').unwrap_or((trimmed, ""));
+    *rest = remaining;
+    Some(word)
+}
+
+/// Parses a range such as `1`, `..5` or `1..5` into its inclusive bounds
+fn parse_range(range:&str) -> Option<(i32, i32)> {
+    match range.split_once("..") {
+        Some((min, max)) => Some((
+            if min.is_empty() {i32::MIN} else {min.parse().ok()?},
+            if max.is_empty() {i32::MAX} else {max.parse().ok()?}
+        )),
+        None => range.parse().ok().map(|v| (v, v))
+    }
+}
+
+/// The division of Java's `Math.floorDiv`, which wraps `i32::MIN / -1` around
+fn floor_div(left:i32, right:i32) -> i32 {
+    let quotient = left.wrapping_div(right);
+    if (left.wrapping_rem(right) != 0) && ((left < 0) != (right < 0)) {
+        quotient - 1
+    } else {
+        quotient
+    }
+}
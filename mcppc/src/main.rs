@@ -0,0 +1,284 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use mcpp_core::compiler::ast::serialiser::MCFunction;
+use mcpp_core::compiler::diagnostics::Diagnostic;
+use mcpp_core::compiler::Compiler;
+use mcpp_core::compiler::save::DatapackExporter;
+use mcpp_core::compiler::target::TargetVersion;
+
+const USAGE:&str = "\
+Usage: mcppc <command> [options] <files...>
+
+Commands:
+    build    Compiles the files into a datapack, whose main functions run on load
+    check    Compiles the files without writing anything
+    emit     Compiles the files and prints the mcfunctions to stdout
+
+Options:
+    -n, --namespace <name>    The namespace of the functions (default: the file name)
+    -o, --out <path>          The output of build (default: out, or out.zip with --zip)
+    -z, --zip                 Writes the datapack as a zip file
+    -T, --target <version>    The version of Minecraft such as 1.20.4, which decides the commands used (default: 1.21)
+    -p, --pack-format <n>     The pack format of the datapack (default: the one of the target)
+    -d, --description <text>  The description of the datapack
+    -t, --tick <function>     Runs the function such as `namespace:tick` every tick
+    -s, --strict              Rejects dropping digits implicitly, e.g. a float assigned to an int without `as`
+    -h, --help                Prints this message";
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Build,
+    Check,
+    Emit
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    command: Command,
+    namespace: Option<String>,
+    out: Option<PathBuf>,
+    zip: bool,
+    target: TargetVersion,
+    pack_format: Option<u32>,
+    description: Option<String>,
+    tick: Vec<String>,
+    strict: bool,
+    sources: Vec<PathBuf>
+}
+impl Options {
+    fn parse(mut args:impl Iterator<Item = String>) -> Result<Options, String> {
+        let command = match args.next().as_deref() {
+            Some("build") => Command::Build,
+            Some("check") => Command::Check,
+            Some("emit") => Command::Emit,
+            Some(c) => return Err(format!("unknown command `{}`", c)),
+            None => return Err("no command was given".to_string())
+        };
+        let mut options = Options {
+            command,
+            namespace: None,
+            out: None,
+            zip: false,
+            target: TargetVersion::default(),
+            pack_format: None,
+            description: None,
+            tick: Vec::new(),
+            strict: false,
+            sources: Vec::new()
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-n" | "--namespace" => options.namespace = Some(
+                    args.next().ok_or(format!("`{}` expects a namespace", arg))?
+                ),
+                "-o" | "--out" => options.out = Some(PathBuf::from(
+                    args.next().ok_or(format!("`{}` expects a path", arg))?
+                )),
+                "-z" | "--zip" => options.zip = true,
+                "-T" | "--target" => options.target = args
+                    .next()
+                    .ok_or(format!("`{}` expects a version", arg))?
+                    .parse()?,
+                "-p" | "--pack-format" => options.pack_format = Some(args
+                    .next()
+                    .and_then(|p| p.parse().ok())
+                    .ok_or(format!("`{}` expects a number", arg))?),
+                "-d" | "--description" => options.description = Some(
+                    args.next().ok_or(format!("`{}` expects a text", arg))?
+                ),
+                "-t" | "--tick" => options.tick.push(
+                    args.next().ok_or(format!("`{}` expects a function", arg))?
+                ),
+                "-s" | "--strict" => options.strict = true,
+                _ if arg.starts_with('-') => return Err(format!("unknown option `{}`", arg)),
+                _ => options.sources.push(PathBuf::from(arg))
+            }
+        }
+        if options.sources.is_empty() {
+            return Err("no source file was given".to_string());
+        }
+        options.check_namespaces()?;
+        Ok(options)
+    }
+    fn pack_format(&self) -> u32 {
+        self.pack_format.unwrap_or(self.target.pack_format())
+    }
+    /// Warns that the pack format of the datapack is guessed, unless it's given with -p
+    fn pack_format_warning(&self) -> Option<String> {
+        match (&self.command, self.pack_format) {
+            (Command::Build, None) if !self.target.is_known() => Some(format!(
+                "the pack format of Minecraft {} isn't known, so the one of {}, {}, is used; give the right one with -p",
+                self.target, TargetVersion::NEWEST, self.pack_format()
+            )),
+            _ => None
+        }
+    }
+    fn out(&self) -> PathBuf {
+        match &self.out {
+            Some(o) => o.clone(),
+            None => PathBuf::from(if self.zip {"out.zip"} else {"out"})
+        }
+    }
+    /// The namespace given, or the lowercased file name of the source
+    fn namespace_of(&self, source:&Path) -> String {
+        match &self.namespace {
+            Some(n) => n.clone(),
+            None => source
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_lowercase())
+                .unwrap_or_else(|| "mcpp".to_string())
+        }
+    }
+    /// Fails if a namespace can't be used in Minecraft,
+    /// or two sources share one, whose `main` and `__load` would overwrite each other's
+    fn check_namespaces(&self) -> Result<(), String> {
+        for (i, source) in self.sources.iter().enumerate() {
+            let namespace = self.namespace_of(source);
+            if namespace.is_empty() || !namespace.chars().all(|c| matches!(c, 'a'..='z' | '0'..='9' | '_' | '-' | '.')) {
+                return Err(format!(
+                    "`{}` of {} isn't a namespace, which consists of a-z, 0-9, `_`, `-` and `.`; give one with -n",
+                    namespace, source.display()
+                ));
+            }
+            if let Some(other) = self.sources[..i].iter().find(|s| self.namespace_of(s) == namespace) {
+                return Err(format!(
+                    "both {} and {} are compiled into the namespace `{}`, whose functions would overwrite each other",
+                    other.display(), source.display(), namespace
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn parse_test() {
+    let args = |s:&str| s.split_whitespace().map(String::from).collect::<Vec<String>>().into_iter();
+    let options = Options::parse(args("build -o pack -n demo a.mcpp -p 15 -t demo:tick")).unwrap();
+    assert_eq!(options.command, Command::Build);
+    assert_eq!(options.out(), PathBuf::from("pack"));
+    assert_eq!((options.pack_format(), options.tick.len()), (15, 1));
+    assert_eq!(options.namespace_of(Path::new("a.mcpp")), "demo");
+    assert_eq!(Options::parse(args("build a.mcpp b.mcpp")).unwrap().sources.len(), 2);
+    // The sources compiled into the same namespace would overwrite each other's functions
+    assert!(Options::parse(args("build -n demo a.mcpp b.mcpp")).is_err());
+    assert!(Options::parse(args("build src/a.mcpp lib/A.mcpp")).is_err());
+    // A namespace has to be valid in Minecraft, whether it's given or the file name
+    assert!(Options::parse(args("build my+pack.mcpp")).is_err());
+    assert!(Options::parse(args("build -n Demo a.mcpp")).is_err());
+    assert!(Options::parse(args("build -n my+pack.x my+pack.mcpp")).is_err());
+    assert!(Options::parse(args("build -n my_pack my+pack.mcpp")).is_ok());
+    let options = Options::parse(args("emit src/Main.mcpp")).unwrap();
+    assert_eq!(options.namespace_of(&options.sources[0]), "main");
+    assert!(Options::parse(args("check")).is_err());
+    assert!(Options::parse(args("run a.mcpp")).is_err());
+    assert!(Options::parse(args("build a.mcpp -o")).is_err());
+    assert!(Options::parse(args("build a.mcpp -p new")).is_err());
+    assert_eq!(Options::parse(args("build -z a.mcpp")).unwrap().out(), PathBuf::from("out.zip"));
+    assert!(Options::parse(args("check --strict a.mcpp")).unwrap().strict);
+    let options = Options::parse(args("build -T 1.20.4 a.mcpp")).unwrap();
+    assert_eq!((options.target, options.pack_format()), (TargetVersion::new(20, 4), 26));
+    assert!(Options::parse(args("build --target 1.12 a.mcpp")).is_err());
+    // A version newer than the known ones is built with the last pack format known, warning it
+    let options = Options::parse(args("build --target 1.22 a.mcpp")).unwrap();
+    assert_eq!(options.pack_format(), TargetVersion::NEWEST.pack_format());
+    assert!(options.pack_format_warning().is_some());
+    assert!(Options::parse(args("build -T 1.22 -p 80 a.mcpp")).unwrap().pack_format_warning().is_none());
+    assert!(Options::parse(args("build -T 1.21.5 a.mcpp")).unwrap().pack_format_warning().is_none());
+}
+
+/// Compiles a file, and prints its diagnostics to stderr if it fails
+fn compile_file(source:&Path, namespace:&str, options:&Options) -> Option<Vec<MCFunction>> {
+    let origin = source.to_string_lossy();
+    let input = match fs::read_to_string(source) {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("error: couldn't read {}: {}", origin, e);
+            return None;
+        }
+    };
+    let mut compiler = Compiler::from(namespace);
+    compiler.strict = options.strict;
+    compiler.target = options.target;
+    match mcpp_core::compile_with(compiler, &input) {
+        Ok(o) => Some(o),
+        Err(e) => {
+            for diagnostic in Diagnostic::all(&e) {
+                eprintln!("{}\n", diagnostic.render(&input, &origin));
+            }
+            None
+        }
+    }
+}
+
+fn build(options:&Options, compiled:&[(String, Vec<MCFunction>)]) -> std::io::Result<()> {
+    let mut exporter = DatapackExporter::new().pack_format(options.pack_format());
+    if let Some(description) = &options.description {
+        exporter = exporter.description(description);
+    }
+    for (namespace, mcfunctions) in compiled {
+        exporter = exporter.functions(namespace, mcfunctions);
+        // The last one sets the objectives up, which has to run before the main, the first one
+        if let (Some(main), Some(load)) = (mcfunctions.first(), mcfunctions.last()) {
+            exporter = exporter
+                .on_load(&format!("{}:{}", namespace, load.get_full_path()))
+                .on_load(&format!("{}:{}", namespace, main.get_full_path()));
+        }
+    }
+    for tick in &options.tick {
+        exporter = exporter.on_tick(tick);
+    }
+    if options.zip {
+        exporter.export_zip(&options.out())
+    } else {
+        exporter.export_dir(&options.out())
+    }
+}
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    if args.is_empty() || args.iter().any(|a| a == "-h" || a == "--help") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    let options = match Options::parse(args.into_iter()) {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    if let Some(warning) = options.pack_format_warning() {
+        eprintln!("warning: {}\n", warning);
+    }
+
+    // Every file is compiled to report all of the errors at once
+    let mut compiled = Vec::new();
+    let mut failed = false;
+    for source in &options.sources {
+        let namespace = options.namespace_of(source);
+        match compile_file(source, &namespace, &options) {
+            Some(mcfunctions) => compiled.push((namespace, mcfunctions)),
+            None => failed = true
+        }
+    }
+    if failed {
+        return ExitCode::FAILURE;
+    }
+
+    match options.command {
+        Command::Check => (),
+        Command::Emit => for (namespace, mcfunctions) in &compiled {
+            for mcfunction in mcfunctions {
+                println!("# {}:{}\n{}\n", namespace, mcfunction.get_full_path(), mcfunction.inside);
+            }
+        },
+        Command::Build => if let Err(e) = build(&options, &compiled) {
+            eprintln!("error: couldn't write into {}: {}", options.out().to_string_lossy(), e);
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}